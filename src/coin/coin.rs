@@ -1,8 +1,15 @@
+use::bevy::ecs::system::SystemParam;
 use::bevy::prelude::*;
 use bevy_asset_loader::prelude::*;
 use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioSource as KiraAudioSource};
+use serde::{Deserialize, Serialize};
 
-use crate::{gravity::gravity::Gravity, movement::movement::{Body, Movement}, player::player::Player, CleanupGameStateExit, GameState};
+use crate::collision::collision::{Collider, CollisionEvent, CollisionLayer, CollisionLayers};
+use crate::difficulty::difficulty::ActiveDifficulty;
+use crate::knife::knife::{Knife, Stuck};
+use crate::powerups::powerups::PowerUp;
+use crate::terrain::terrain::Terrain;
+use crate::{gravity::gravity::Gravity, movement::movement::{Body, Movement, PhysicsSet}, player::player::Player, CleanupGameStateExit, GameState, GameplaySet, PauseState};
 
 pub struct CoinPlugin<GameState: States> {
     pub state: GameState
@@ -11,11 +18,33 @@ pub struct CoinPlugin<GameState: States> {
 impl Plugin for CoinPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.add_event::<CoinTouchedEvent>();
+        app.add_event::<PowerUpCollectedEvent>();
         app.add_audio_channel::<CoinChannel>();
-        app.add_systems(Update, (check_if_touch_player, animate_sprite).run_if(in_state(self.state.clone())));
+        app.add_systems(Update, (animate_sprite, expire_on_ground).in_set(GameplaySet::World).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        // Knives knock pickups away after the ground friction, so the friction does not eat the knock
+        app.add_systems(FixedUpdate, (check_if_touch_player, slow_down_on_terrain, react_to_knives).chain().in_set(PhysicsSet::Collide)
+            .run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
+// Share of the landing speed a pickup bounces back up with
+const BOUNCE: f32 = 0.4;
+// Sideways speed kept every physics tick on the ground
+const GROUND_FRICTION: f32 = 0.85;
+// Seconds before expiring a pickup starts blinking
+const BLINK_TIME: f32 = 2.0;
+const BLINKS_PER_SECOND: f32 = 6.0;
+const KNOCK_AWAY_VELOCITY: Vec2 = Vec2::new(350.0, 300.0);
+
+// What happens to a coin or power-up a knife falls through
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum KnifeCoinRule {
+    #[default]
+    Ignore,
+    KnockAway,
+    Destroy
+}
+
 #[derive(AssetCollection, Resource)]
 pub struct CoinAssets {
     #[asset(texture_atlas_layout(tile_size_x = 16, tile_size_y = 16, columns = 4, rows = 1,))]
@@ -36,9 +65,11 @@ pub struct CoinChannel;
 pub struct CoinBundle {
     coin: Coin,
     movement: Movement,
+    collider: Collider,
     sprite: SpriteBundle,
     atlas: TextureAtlas,
-    animation_timer: AnimationTimer
+    animation_timer: AnimationTimer,
+    ground_time: GroundTime
 }
 
 #[derive(Component)]
@@ -48,65 +79,241 @@ pub struct Coin {
 
 #[derive(Event)]
 pub struct CoinTouchedEvent {
-    pub value: i32
+    pub value: i32,
+    pub position: Vec3
+}
+
+// Rarer coins are worth more
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum CoinTier {
+    Bronze,
+    Silver,
+    Gold
+}
+
+impl CoinTier {
+    pub const ALL: [CoinTier; 3] = [CoinTier::Bronze, CoinTier::Silver, CoinTier::Gold];
+
+    pub fn value(&self) -> i32 {
+        match self {
+            CoinTier::Bronze => 5,
+            CoinTier::Silver => 10,
+            CoinTier::Gold => 25
+        }
+    }
+
+    // Chance of dropping relative to the other tiers
+    pub fn weight(&self) -> f32 {
+        match self {
+            CoinTier::Bronze => 6.0,
+            CoinTier::Silver => 3.0,
+            CoinTier::Gold => 1.0
+        }
+    }
+
+    // The sprite is gold, the other tiers tint it
+    fn tint(&self) -> Color {
+        match self {
+            CoinTier::Bronze => Color::srgb(0.85, 0.5, 0.3),
+            CoinTier::Silver => Color::srgb(0.75, 0.8, 0.9),
+            CoinTier::Gold => Color::WHITE
+        }
+    }
+}
+
+// A power-up falls and gets picked up like a coin, but starts a timed effect instead of giving points
+#[derive(Component)]
+pub struct PowerUpPickup {
+    pub power_up: PowerUp
+}
+
+#[derive(Event)]
+pub struct PowerUpCollectedEvent {
+    pub power_up: PowerUp
 }
 
 #[derive(Component)]
 struct AnimationTimer(Timer);
 
-pub fn spawn_coin(mut commands: Commands, animations: Res<CoinAssets>, spawn_position: Vec3) {
+// Seconds a pickup has been lying on the ground, it expires after the lifetime of the difficulty
+#[derive(Component, Default)]
+struct GroundTime(f32);
+
+pub fn spawn_coin(mut commands: Commands, animations: Res<CoinAssets>, spawn_position: Vec3, tier: CoinTier) {
     commands.spawn(CoinBundle {
-        coin: Coin { value: 10 },
-        sprite: SpriteBundle {
-            texture: animations.idle.clone(),
-            sprite: Sprite {
-                custom_size: Some(Vec2::new(16.0, 16.0)),
-                ..default()
-            },
-            transform: Transform {
-                scale: Vec3::splat(4.0),
-                translation: spawn_position,
-                ..default()
-            },
-            ..default()
-        },
+        coin: Coin { value: tier.value() },
+        sprite: pickup_sprite(&animations, spawn_position, tier.tint()),
         movement: Movement {
             gravity: Gravity {
                 is_touching_terrain: false
             },
-            body: Body::default()
+            body: pickup_body()
         },
+        collider: pickup_collider(),
         atlas: TextureAtlas::from(animations.layout.clone()),
-        animation_timer: AnimationTimer(Timer::from_seconds(0.125, TimerMode::Repeating))
+        animation_timer: AnimationTimer(Timer::from_seconds(0.125, TimerMode::Repeating)),
+        ground_time: GroundTime::default()
     }).insert(CleanupGameStateExit);
 }
 
-fn check_if_touch_player(mut commands: Commands, mut ev_coin_collected: EventWriter<CoinTouchedEvent>, coin_query: Query<(&Movement, &Transform, &Sprite, &Coin, Entity)>, player_query: Query<(&Transform, &Sprite, &Player)>,
-    coin_assets: Res<CoinAssets>, coin_channel: Res<AudioChannel<CoinChannel>>) {
+// Uses the coin animation tinted in the color of the power-up
+pub fn spawn_power_up(mut commands: Commands, animations: Res<CoinAssets>, spawn_position: Vec3, power_up: PowerUp) {
+    commands.spawn((
+        PowerUpPickup { power_up },
+        pickup_sprite(&animations, spawn_position, power_up.color()),
+        Movement {
+            gravity: Gravity {
+                is_touching_terrain: false
+            },
+            body: pickup_body()
+        },
+        pickup_collider(),
+        TextureAtlas::from(animations.layout.clone()),
+        AnimationTimer(Timer::from_seconds(0.125, TimerMode::Repeating)),
+        GroundTime::default()
+    )).insert((Name::new(power_up.label()), CleanupGameStateExit));
+}
 
-    if let Ok((player_transform, player_sprite, _player)) = player_query.get_single() {
-        let player_half_size = player_sprite.custom_size.unwrap() * player_transform.scale.truncate() / 2.0;
+fn pickup_sprite(animations: &CoinAssets, spawn_position: Vec3, color: Color) -> SpriteBundle {
+    SpriteBundle {
+        texture: animations.idle.clone(),
+        sprite: Sprite {
+            color,
+            custom_size: Some(Vec2::new(16.0, 16.0)),
+            ..default()
+        },
+        transform: Transform {
+            scale: Vec3::splat(4.0),
+            translation: spawn_position,
+            ..default()
+        },
+        ..default()
+    }
+}
 
-        for (_movement, transform, sprite, coin, entity) in coin_query.iter() {
-            let coin_half_size = sprite.custom_size.unwrap() * transform.scale.truncate() / 2.0;
-            
-            let distance = transform.translation - player_transform.translation;
+fn pickup_body() -> Body {
+    Body { restitution: BOUNCE, ..default() }
+}
 
-            if distance.x.abs() < coin_half_size.x + player_half_size.x &&
-                       distance.y.abs() < coin_half_size.y + player_half_size.y {
-                        ev_coin_collected.send(CoinTouchedEvent { value: coin.value });
-                        coin_channel.play(coin_assets.coin_collected_sound.clone()).with_volume(0.12);
-                        commands.entity(entity).despawn();
+fn pickup_collider() -> Collider {
+    Collider::new(Vec2::new(20.0, 20.0), CollisionLayers::new(CollisionLayer::COIN, CollisionLayer::PLAYER | CollisionLayer::KNIFE | CollisionLayer::TERRAIN))
+}
+
+// Coins and power-ups lying around to be picked up
+type Pickups = Or<(With<Coin>, With<PowerUpPickup>)>;
+
+#[derive(SystemParam)]
+struct PickupSound<'w> {
+    coin_assets: Res<'w, CoinAssets>,
+    coin_channel: Res<'w, AudioChannel<CoinChannel>>
+}
+
+impl PickupSound<'_> {
+    fn play(&self) {
+        self.coin_channel.play(self.coin_assets.coin_collected_sound.clone()).with_volume(0.12);
+    }
+}
+
+fn check_if_touch_player(mut commands: Commands, mut ev_collision: EventReader<CollisionEvent>, mut ev_coin_collected: EventWriter<CoinTouchedEvent>,
+    mut ev_power_up_collected: EventWriter<PowerUpCollectedEvent>, pickup_query: Query<(AnyOf<(&Coin, &PowerUpPickup)>, &Transform)>, player_query: Query<(), With<Player>>,
+    pickup_sound: PickupSound) {
+
+    for event in ev_collision.read() {
+        for (entity, other) in event.pairs() {
+            if let Ok(((coin, power_up), transform)) = pickup_query.get(entity) {
+                if player_query.contains(other) {
+                    if let Some(coin) = coin {
+                        ev_coin_collected.send(CoinTouchedEvent { value: coin.value, position: transform.translation });
+                    }
+
+                    if let Some(power_up) = power_up {
+                        ev_power_up_collected.send(PowerUpCollectedEvent { power_up: power_up.power_up });
                     }
+
+                    pickup_sound.play();
+                    commands.entity(entity).despawn();
+                }
+            }
         }
     }
 }
 
-fn animate_sprite(time: Res<Time>, mut query: Query<(&mut AnimationTimer, &mut TextureAtlas), With<Coin>>) {
+fn animate_sprite(time: Res<Time>, mut query: Query<(&mut AnimationTimer, &mut TextureAtlas), Pickups>) {
     for (mut timer, mut sprite) in &mut query {
         timer.0.tick(time.delta());
         if timer.0.just_finished() {
             sprite.index = (sprite.index + 1) % 4;
         }
     }
-}
\ No newline at end of file
+}
+
+// Bouncing is left to the terrain collision, every contact only takes away sideways speed
+fn slow_down_on_terrain(mut ev_collision: EventReader<CollisionEvent>, mut pickup_query: Query<&mut Movement, Pickups>,
+    terrain_query: Query<(), With<Terrain>>) {
+
+    for event in ev_collision.read() {
+        for (entity, other) in event.pairs() {
+            let Ok(mut movement) = pickup_query.get_mut(entity) else {
+                continue;
+            };
+
+            if !terrain_query.contains(other) {
+                continue;
+            }
+
+            movement.body.velocity.x *= GROUND_FRICTION;
+        }
+    }
+}
+
+fn react_to_knives(mut commands: Commands, mut ev_collision: EventReader<CollisionEvent>, mut pickup_query: Query<(&mut Movement, &Transform), Pickups>,
+    knife_query: Query<&Transform, (With<Knife>, Without<Stuck>)>, difficulty: Res<ActiveDifficulty>) {
+
+    let rule = difficulty.profile.knives_hit_coins;
+
+    if rule == KnifeCoinRule::Ignore {
+        return;
+    }
+
+    for event in ev_collision.read() {
+        for (entity, other) in event.pairs() {
+            let (Ok((mut movement, transform)), Ok(knife_transform)) = (pickup_query.get_mut(entity), knife_query.get(other)) else {
+                continue;
+            };
+
+            match rule {
+                KnifeCoinRule::KnockAway => {
+                    let side = if transform.translation.x < knife_transform.translation.x { -1.0 } else { 1.0 };
+                    movement.body.velocity = KNOCK_AWAY_VELOCITY * Vec2::new(side, 1.0);
+                },
+                KnifeCoinRule::Destroy => commands.entity(entity).despawn(),
+                KnifeCoinRule::Ignore => {}
+            }
+        }
+    }
+}
+
+fn expire_on_ground(mut commands: Commands, time: Res<Time>, difficulty: Res<ActiveDifficulty>,
+    mut pickup_query: Query<(Entity, &Movement, &mut GroundTime, &mut Visibility)>) {
+
+    let lifetime = difficulty.profile.coin_lifetime;
+
+    for (entity, movement, mut ground_time, mut visibility) in &mut pickup_query {
+        if movement.gravity.is_touching_terrain {
+            ground_time.0 += time.delta_seconds();
+        }
+
+        let remaining = lifetime - ground_time.0;
+
+        if remaining <= 0.0 {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        *visibility = if remaining < BLINK_TIME && (remaining * BLINKS_PER_SECOND) as i32 % 2 == 0 {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        };
+    }
+}
@@ -1,21 +1,29 @@
+use std::time::Duration;
+
 use::bevy::prelude::*;
-use bevy::window::PrimaryWindow;
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
 
-use crate::GameState;
+use crate::difficulty::difficulty::{apply_difficulty, ActiveDifficulty, DifficultyProfile};
+use crate::random::random::{GameRng, RngStream};
+use crate::{GameState, GameplaySet, Level, LevelUpEvent, PauseState, PlayField};
+
+use crate::powerups::powerups::PowerUp;
 
-use super::coin::{spawn_coin, CoinAssets};
+use super::coin::{spawn_coin, spawn_power_up, CoinAssets, CoinTier};
 
 pub struct CoinSpawnerPlugin<GameState: States> {
     pub state: GameState
 }
 
-const COIN_SPAWN_TIMER: f32 = 3.0;
+// Chance of a power-up dropping instead of a coin
+const POWER_UP_CHANCE: f64 = 0.12;
 
 impl Plugin for CoinSpawnerPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.init_resource::<CoinSpawnTimer>();
-        app.add_systems(Update, (tick_coin_spawn_timer, spawn_coin_over_time).run_if(in_state(self.state.clone())));
+        app.add_systems(OnEnter(GameState::Game), reset_coin_spawn_timer.after(apply_difficulty));
+        app.add_systems(Update, (listen_for_level_up, tick_coin_spawn_timer, spawn_coin_over_time).chain().in_set(GameplaySet::Spawn).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
@@ -26,7 +34,25 @@ pub struct CoinSpawnTimer {
 
 impl Default for CoinSpawnTimer {
     fn default() -> Self {
-        Self { timer: Timer::from_seconds(COIN_SPAWN_TIMER, TimerMode::Repeating) }
+        Self::for_level(&DifficultyProfile::default(), 0)
+    }
+}
+
+impl CoinSpawnTimer {
+    fn for_level(profile: &DifficultyProfile, level: i32) -> Self {
+        Self { timer: Timer::from_seconds(profile.coin_spawn_interval.at(level), TimerMode::Repeating) }
+    }
+}
+
+fn reset_coin_spawn_timer(mut coin_spawn_timer: ResMut<CoinSpawnTimer>, difficulty: Res<ActiveDifficulty>) {
+    *coin_spawn_timer = CoinSpawnTimer::for_level(&difficulty.profile, 0);
+}
+
+// Keeps the time already passed, so a shorter interval does not restart the wait for the next coin
+fn listen_for_level_up(mut level_up_event_reader: EventReader<LevelUpEvent>, mut coin_spawn_timer: ResMut<CoinSpawnTimer>, level: Res<Level>, difficulty: Res<ActiveDifficulty>) {
+    for _ in level_up_event_reader.read() {
+        let interval = difficulty.profile.coin_spawn_interval.at(level.value);
+        coin_spawn_timer.timer.set_duration(Duration::from_secs_f32(interval));
     }
 }
 
@@ -34,13 +60,24 @@ fn tick_coin_spawn_timer(mut coin_spawn_timer: ResMut<CoinSpawnTimer>, time: Res
     coin_spawn_timer.timer.tick(time.delta());
 }
 
-fn spawn_coin_over_time(commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>, coin_spawn_timer: Res<CoinSpawnTimer>, asset: Res<CoinAssets>) {
+fn spawn_coin_over_time(commands: Commands, play_field: Res<PlayField>, mut game_rng: ResMut<GameRng>, coin_spawn_timer: Res<CoinSpawnTimer>, asset: Res<CoinAssets>) {
     if coin_spawn_timer.timer.finished() {
-        let window = window_query.get_single().unwrap();
+        let rng = game_rng.stream(RngStream::Coins);
+        let random_x = rng.gen_range((-play_field.width/2.0)+16.0..(play_field.width/2.0)-16.0);
+        let spawn_position = Vec3::new(random_x, (play_field.height / 2.0) - 16.0, 0.0);
 
-        let random_x = rand::thread_rng().gen_range((-window.width()/2.0)+16.0..(window.width()/2.0)-16.0);
-        let spawn_y = (window.height() / 2.0) - 16.0;
+        if rng.gen_bool(POWER_UP_CHANCE) {
+            let power_up = PowerUp::ALL[rng.gen_range(0..PowerUp::ALL.len())];
+            spawn_power_up(commands, asset, spawn_position, power_up);
+        } else {
+            spawn_coin(commands, asset, spawn_position, choose_coin_tier(rng));
+        }
+    }
+}
 
-        spawn_coin(commands, asset, Vec3::new(random_x, spawn_y, 0.0))
+fn choose_coin_tier(rng: &mut impl Rng) -> CoinTier {
+    match WeightedIndex::new(CoinTier::ALL.map(|tier| tier.weight())) {
+        Ok(index) => CoinTier::ALL[index.sample(rng)],
+        Err(_) => CoinTier::Silver
     }
-}
\ No newline at end of file
+}
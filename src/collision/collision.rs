@@ -0,0 +1,116 @@
+use::bevy::prelude::*;
+
+use crate::{movement::movement::PhysicsSet, GameState, PauseState};
+
+pub struct CollisionPlugin<GameState: States> {
+    pub state: GameState
+}
+
+impl Plugin for CollisionPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<CollisionEvent>();
+        app.add_systems(FixedUpdate, detect_collisions.in_set(PhysicsSet::Detect).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+    }
+}
+
+pub struct CollisionLayer;
+
+impl CollisionLayer {
+    pub const PLAYER: u32 = 1 << 0;
+    pub const KNIFE: u32 = 1 << 1;
+    pub const COIN: u32 = 1 << 2;
+    pub const TERRAIN: u32 = 1 << 3;
+}
+
+// An entity is in the `memberships` layers and collides with everything in its `filters`
+#[derive(Clone, Copy)]
+pub struct CollisionLayers {
+    pub memberships: u32,
+    pub filters: u32
+}
+
+impl CollisionLayers {
+    pub fn new(memberships: u32, filters: u32) -> Self {
+        Self { memberships, filters }
+    }
+
+    fn interacts_with(&self, other: &CollisionLayers) -> bool {
+        self.filters & other.memberships != 0 || other.filters & self.memberships != 0
+    }
+}
+
+// Hitbox in world units, it does not follow the sprite size or the transform scale
+// It stays axis aligned unless `rotates` is set, then it turns with the transform rotation
+#[derive(Component, Clone, Copy)]
+pub struct Collider {
+    pub half_size: Vec2,
+    pub offset: Vec2,
+    pub layers: CollisionLayers,
+    pub rotates: bool
+}
+
+impl Collider {
+    pub fn new(half_size: Vec2, layers: CollisionLayers) -> Self {
+        Self { half_size, offset: Vec2::ZERO, layers, rotates: false }
+    }
+
+    pub fn rotating(mut self) -> Self {
+        self.rotates = true;
+        self
+    }
+
+    pub fn center(&self, transform: &Transform) -> Vec2 {
+        transform.translation.truncate() + self.offset
+    }
+
+    fn axes(&self, transform: &Transform) -> [Vec2; 2] {
+        if self.rotates {
+            [(transform.rotation * Vec3::X).truncate(), (transform.rotation * Vec3::Y).truncate()]
+        } else {
+            [Vec2::X, Vec2::Y]
+        }
+    }
+
+    // Half of the length of the hitbox projected on `axis`
+    fn projected_radius(&self, axes: &[Vec2; 2], axis: Vec2) -> f32 {
+        self.half_size.x * axes[0].dot(axis).abs() + self.half_size.y * axes[1].dot(axis).abs()
+    }
+
+    // Half size of the axis aligned box around the hitbox
+    pub fn half_extents(&self, transform: &Transform) -> Vec2 {
+        let axes = self.axes(transform);
+        Vec2::new(self.projected_radius(&axes, Vec2::X), self.projected_radius(&axes, Vec2::Y))
+    }
+
+    // Separating axis test, two boxes overlap unless a gap shows on one of their edge directions
+    pub fn overlaps(&self, transform: &Transform, other: &Collider, other_transform: &Transform) -> bool {
+        let distance = self.center(transform) - other.center(other_transform);
+        let axes = self.axes(transform);
+        let other_axes = other.axes(other_transform);
+
+        axes.iter().chain(other_axes.iter()).all(|axis| {
+            distance.dot(*axis).abs() < self.projected_radius(&axes, *axis) + other.projected_radius(&other_axes, *axis)
+        })
+    }
+}
+
+#[derive(Event)]
+pub struct CollisionEvent {
+    pub a: Entity,
+    pub b: Entity
+}
+
+impl CollisionEvent {
+    // Both orderings of the pair, so consumers can match on (mine, other) without caring who is `a`
+    pub fn pairs(&self) -> [(Entity, Entity); 2] {
+        [(self.a, self.b), (self.b, self.a)]
+    }
+}
+
+fn detect_collisions(collider_query: Query<(Entity, &Collider, &Transform)>, mut ev_collision: EventWriter<CollisionEvent>) {
+    for [(a, a_collider, a_transform), (b, b_collider, b_transform)] in collider_query.iter_combinations() {
+        if a_collider.layers.interacts_with(&b_collider.layers) && a_collider.overlaps(a_transform, b_collider, b_transform) {
+            ev_collision.send(CollisionEvent { a, b });
+        }
+    }
+}
@@ -0,0 +1,266 @@
+use std::collections::HashMap;
+
+use::bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::storage::{self, LoadError};
+
+pub struct ControlsPlugin;
+
+const CONTROLS_FILE: &str = "controls.ron";
+const CONTROLS_VERSION: u32 = 1;
+pub const BINDING_SLOTS: usize = 2;
+
+impl Plugin for ControlsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ActionMap::load());
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    Jump,
+    Dash,
+    Pause
+}
+
+impl Action {
+    pub const ALL: [Action; 5] = [Action::MoveLeft, Action::MoveRight, Action::Jump, Action::Dash, Action::Pause];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move Left",
+            Action::MoveRight => "Move Right",
+            Action::Jump => "Jump",
+            Action::Dash => "Dash",
+            Action::Pause => "Pause",
+        }
+    }
+
+    fn default_keys(&self) -> [Option<KeyCode>; BINDING_SLOTS] {
+        match self {
+            Action::MoveLeft => [Some(KeyCode::KeyA), Some(KeyCode::ArrowLeft)],
+            Action::MoveRight => [Some(KeyCode::KeyD), Some(KeyCode::ArrowRight)],
+            Action::Jump => [Some(KeyCode::Space), Some(KeyCode::ArrowUp)],
+            Action::Dash => [Some(KeyCode::ShiftLeft), Some(KeyCode::ArrowDown)],
+            Action::Pause => [Some(KeyCode::Escape), Some(KeyCode::KeyP)],
+        }
+    }
+}
+
+// How opposite movement keys held at the same time are resolved
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum AxisPolicy {
+    #[default]
+    LastPressed,
+    FirstPressed,
+    CancelOut
+}
+
+impl AxisPolicy {
+    pub fn label(&self) -> &'static str {
+        match self {
+            AxisPolicy::LastPressed => "Last pressed wins",
+            AxisPolicy::FirstPressed => "First pressed wins",
+            AxisPolicy::CancelOut => "Cancel out",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            AxisPolicy::LastPressed => AxisPolicy::FirstPressed,
+            AxisPolicy::FirstPressed => AxisPolicy::CancelOut,
+            AxisPolicy::CancelOut => AxisPolicy::LastPressed,
+        }
+    }
+}
+
+// What binding a key did to the action it was taken from
+#[derive(Clone, Copy, Eq, PartialEq, Debug)]
+pub enum Rebind {
+    Bound,
+    Taken(Action),
+    // The action would have been left without keys, so it got the key that was replaced
+    Swapped(Action, KeyCode),
+    // The key is the last one of the action and there was nothing to swap it with
+    Refused(Action)
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+pub struct ActionMap {
+    pub version: u32,
+    #[serde(default)]
+    pub axis_policy: AxisPolicy,
+    bindings: HashMap<Action, [Option<KeyCode>; BINDING_SLOTS]>
+}
+
+impl Default for ActionMap {
+    fn default() -> Self {
+        Self {
+            version: CONTROLS_VERSION,
+            axis_policy: AxisPolicy::default(),
+            bindings: Action::ALL.iter().map(|action| (*action, action.default_keys())).collect()
+        }
+    }
+}
+
+impl ActionMap {
+    pub fn load() -> Self {
+        match storage::load_ron::<ActionMap>(CONTROLS_FILE) {
+            Ok(mut action_map) if action_map.version == CONTROLS_VERSION => {
+                // Actions added after the file was written keep their default keys
+                for action in Action::ALL {
+                    action_map.bindings.entry(action).or_insert_with(|| action.default_keys());
+                }
+                action_map
+            },
+            Ok(action_map) => {
+                warn!("Controls file has unsupported version {}, using the default controls", action_map.version);
+                Self::default()
+            },
+            Err(LoadError::Missing) => Self::default(),
+            Err(LoadError::Corrupt(error)) => {
+                warn!("Controls file is corrupt ({error}), using the default controls");
+                if let Err(error) = storage::backup_corrupt(CONTROLS_FILE) {
+                    warn!("Could not back up controls file: {error}");
+                }
+                Self::default()
+            },
+            Err(LoadError::Io(error)) => {
+                warn!("Could not read controls file: {error}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = storage::save_ron(CONTROLS_FILE, self) {
+            warn!("Could not save controls file: {error}");
+        }
+    }
+
+    pub fn keys(&self, action: Action) -> [Option<KeyCode>; BINDING_SLOTS] {
+        self.bindings.get(&action).copied().unwrap_or_default()
+    }
+
+    pub fn is_bound(&self, action: Action, key: KeyCode) -> bool {
+        self.keys(action).contains(&Some(key))
+    }
+
+    pub fn just_pressed(&self, action: Action, input: &ButtonInput<KeyCode>) -> bool {
+        self.keys(action).iter().flatten().any(|key| input.just_pressed(*key))
+    }
+
+    // Binds the key to the slot and removes it from wherever else it was bound, an action that would lose its
+    // last key gets the replaced key instead, or the bind is refused when the slot was empty
+    pub fn bind(&mut self, action: Action, slot: usize, key: KeyCode) -> Rebind {
+        let replaced = self.keys(action)[slot];
+        let keeps_another_key = |keys: &[Option<KeyCode>; BINDING_SLOTS]| keys.iter().any(|other_key| other_key.is_some() && *other_key != Some(key));
+
+        for (other_action, keys) in self.bindings.iter() {
+            if *other_action != action && keys.contains(&Some(key)) && !keeps_another_key(keys) && replaced.is_none() {
+                return Rebind::Refused(*other_action);
+            }
+        }
+
+        let mut result = Rebind::Bound;
+
+        for (other_action, keys) in self.bindings.iter_mut() {
+            let keeps_key = *other_action == action || keeps_another_key(keys);
+
+            for (other_slot, other_key) in keys.iter_mut().enumerate() {
+                if *other_key != Some(key) || (*other_action, other_slot) == (action, slot) {
+                    continue;
+                }
+
+                if keeps_key {
+                    *other_key = None;
+                    if *other_action != action {
+                        result = Rebind::Taken(*other_action);
+                    }
+                } else if let Some(replaced) = replaced {
+                    *other_key = Some(replaced);
+                    result = Rebind::Swapped(*other_action, replaced);
+                }
+            }
+        }
+
+        self.bindings.entry(action).or_default()[slot] = Some(key);
+        result
+    }
+
+    // The last key of an action stays, so every action can still be reached, returns whether the slot was cleared
+    pub fn clear(&mut self, action: Action, slot: usize) -> bool {
+        let keys = self.keys(action);
+        let other_key_bound = keys.iter().enumerate().any(|(other_slot, key)| other_slot != slot && key.is_some());
+
+        if keys[slot].is_some() && !other_key_bound {
+            return false;
+        }
+
+        self.bindings.entry(action).or_default()[slot] = None;
+        true
+    }
+}
+
+pub fn key_name(key: KeyCode) -> String {
+    let name = format!("{key:?}");
+
+    name.strip_prefix("Key")
+        .or_else(|| name.strip_prefix("Digit"))
+        .unwrap_or(&name)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_free_key_takes_nothing() {
+        let mut action_map = ActionMap::default();
+
+        assert_eq!(action_map.bind(Action::Jump, 1, KeyCode::KeyW), Rebind::Bound);
+        assert_eq!(action_map.keys(Action::Jump), [Some(KeyCode::Space), Some(KeyCode::KeyW)]);
+    }
+
+    #[test]
+    fn binding_a_used_key_takes_it_from_the_other_action() {
+        let mut action_map = ActionMap::default();
+
+        assert_eq!(action_map.bind(Action::Jump, 1, KeyCode::KeyA), Rebind::Taken(Action::MoveLeft));
+        assert_eq!(action_map.keys(Action::Jump), [Some(KeyCode::Space), Some(KeyCode::KeyA)]);
+        assert_eq!(action_map.keys(Action::MoveLeft), [None, Some(KeyCode::ArrowLeft)]);
+    }
+
+    #[test]
+    fn taking_the_last_key_swaps_in_the_replaced_key() {
+        let mut action_map = ActionMap::default();
+        assert!(action_map.clear(Action::MoveLeft, 1));
+
+        assert_eq!(action_map.bind(Action::Jump, 1, KeyCode::KeyA), Rebind::Swapped(Action::MoveLeft, KeyCode::ArrowUp));
+        assert_eq!(action_map.keys(Action::Jump), [Some(KeyCode::Space), Some(KeyCode::KeyA)]);
+        assert_eq!(action_map.keys(Action::MoveLeft), [Some(KeyCode::ArrowUp), None]);
+    }
+
+    #[test]
+    fn taking_the_last_key_into_an_empty_slot_is_refused() {
+        let mut action_map = ActionMap::default();
+        assert!(action_map.clear(Action::MoveLeft, 1));
+        assert!(action_map.clear(Action::Jump, 1));
+
+        assert_eq!(action_map.bind(Action::Jump, 1, KeyCode::KeyA), Rebind::Refused(Action::MoveLeft));
+        assert_eq!(action_map.keys(Action::Jump), [Some(KeyCode::Space), None]);
+        assert_eq!(action_map.keys(Action::MoveLeft), [Some(KeyCode::KeyA), None]);
+    }
+
+    #[test]
+    fn moving_a_key_between_slots_of_one_action_keeps_it_bound() {
+        let mut action_map = ActionMap::default();
+
+        assert_eq!(action_map.bind(Action::MoveLeft, 1, KeyCode::KeyA), Rebind::Bound);
+        assert_eq!(action_map.keys(Action::MoveLeft), [None, Some(KeyCode::KeyA)]);
+    }
+}
\ No newline at end of file
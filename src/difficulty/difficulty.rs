@@ -0,0 +1,412 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+
+use::bevy::asset::io::Reader;
+use::bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use::bevy::prelude::*;
+use bevy_asset_loader::asset_collection::AssetCollection;
+use serde::{Deserialize, Serialize};
+
+use crate::coin::coin::KnifeCoinRule;
+use crate::knife::knife::KnifeKind;
+use crate::replay::replay::ReplayPlayback;
+use crate::settings::settings::{pick_run_settings, RunSettings};
+use crate::storage::storage::{self, LoadError};
+use crate::GameState;
+
+pub struct DifficultyPlugin;
+
+// Players write their own profile here, it starts as a copy of the normal one
+const CUSTOM_DIFFICULTY_FILE: &str = "custom_difficulty.ron";
+
+impl Plugin for DifficultyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DifficultyProfile>();
+        app.init_asset_loader::<DifficultyProfileLoader>();
+        app.init_resource::<ActiveDifficulty>();
+        app.add_systems(OnEnter(GameState::Game), apply_difficulty.after(pick_run_settings));
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Custom
+}
+
+impl Difficulty {
+    pub fn label(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Custom => "Custom",
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            Difficulty::Easy => Difficulty::Normal,
+            Difficulty::Normal => Difficulty::Hard,
+            Difficulty::Hard => Difficulty::Custom,
+            Difficulty::Custom => Difficulty::Easy,
+        }
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct DifficultyAssets {
+    #[asset(path = "difficulty/easy.difficulty.ron")]
+    easy: Handle<DifficultyProfile>,
+
+    #[asset(path = "difficulty/normal.difficulty.ron")]
+    normal: Handle<DifficultyProfile>,
+
+    #[asset(path = "difficulty/hard.difficulty.ron")]
+    hard: Handle<DifficultyProfile>
+}
+
+// Points of (level, value), values between points are blended linearly and the ends carry on flat
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Curve(pub Vec<(f32, f32)>);
+
+impl Curve {
+    pub fn at(&self, level: i32) -> f32 {
+        let level = level as f32;
+
+        let Some(&(first_level, first_value)) = self.0.first() else {
+            return 0.0;
+        };
+
+        if level <= first_level {
+            return first_value;
+        }
+
+        for window in self.0.windows(2) {
+            let ((from_level, from_value), (to_level, to_value)) = (window[0], window[1]);
+
+            if level <= to_level {
+                let blend = if to_level > from_level { (level - from_level) / (to_level - from_level) } else { 1.0 };
+                return from_value + (to_value - from_value) * blend;
+            }
+        }
+
+        self.0.last().map_or(first_value, |&(_, value)| value)
+    }
+
+    // Values are blended between the points, so every level is above zero when every point is
+    fn is_positive(&self) -> bool {
+        !self.0.is_empty() && self.0.iter().all(|&(_, value)| is_positive(value))
+    }
+}
+
+// How a run gets harder as the level rises, every curve takes the level as input
+#[derive(Asset, TypePath, Clone, Serialize, Deserialize)]
+pub struct DifficultyProfile {
+    pub name: String,
+    // Seconds until the next level
+    pub level_duration: Curve,
+    // Seconds between knives outside of waves
+    pub knife_spawn_interval: Curve,
+    // Multiplies the gravity pulling knives down
+    pub knife_fall_speed: Curve,
+    // Seconds the warning marker shows before a knife appears
+    #[serde(default = "default_telegraph_time")]
+    pub telegraph_time: Curve,
+    // Chance of each kind being picked relative to the others, missing kinds never show up
+    pub knife_kinds: HashMap<KnifeKind, Curve>,
+    // Seconds between coins
+    pub coin_spawn_interval: Curve,
+    // Seconds a coin stays on the ground before it disappears
+    #[serde(default = "default_coin_lifetime")]
+    pub coin_lifetime: f32,
+    // What a knife does to the coins it falls through
+    #[serde(default = "default_knives_hit_coins")]
+    pub knives_hit_coins: KnifeCoinRule
+}
+
+fn is_positive(value: f32) -> bool {
+    value.is_finite() && value > 0.0
+}
+
+fn default_coin_lifetime() -> f32 {
+    6.0
+}
+
+fn default_knives_hit_coins() -> KnifeCoinRule {
+    KnifeCoinRule::KnockAway
+}
+
+fn default_telegraph_time() -> Curve {
+    Curve(vec![(0.0, 0.8), (11.0, 0.25)])
+}
+
+// Used when a profile can not be loaded, matches `normal.difficulty.ron`
+impl Default for DifficultyProfile {
+    fn default() -> Self {
+        Self {
+            name: "Normal".to_string(),
+            level_duration: Curve(vec![(0.0, 5.0)]),
+            knife_spawn_interval: Curve(vec![(0.0, 0.2), (15.0, 0.05)]),
+            knife_fall_speed: Curve(vec![(0.0, 1.0)]),
+            telegraph_time: default_telegraph_time(),
+            knife_kinds: HashMap::from([
+                (KnifeKind::Straight, Curve(vec![(0.0, 10.0)])),
+                (KnifeKind::Angled, Curve(vec![(0.0, 0.0), (6.0, 6.0)])),
+                (KnifeKind::Homing, Curve(vec![(2.0, 0.0), (7.0, 4.0)])),
+                (KnifeKind::Spinning, Curve(vec![(1.0, 0.0), (8.0, 5.0)])),
+                (KnifeKind::Splitting, Curve(vec![(4.0, 0.0), (10.0, 3.0)])),
+            ]),
+            coin_spawn_interval: Curve(vec![(0.0, 3.0)]),
+            coin_lifetime: default_coin_lifetime(),
+            knives_hit_coins: default_knives_hit_coins()
+        }
+    }
+}
+
+impl DifficultyProfile {
+    pub fn knife_kind_weight(&self, kind: KnifeKind, level: i32) -> f32 {
+        self.knife_kinds.get(&kind).map_or(0.0, |curve| curve.at(level).max(0.0))
+    }
+
+    // Profiles are edited by hand, timers can not run on zero or negative seconds, knives have to fall down
+    // and an empty curve has no value to give
+    fn validate(&self) -> Result<(), String> {
+        let positive_curves = [
+            ("level_duration", &self.level_duration),
+            ("knife_spawn_interval", &self.knife_spawn_interval),
+            ("knife_fall_speed", &self.knife_fall_speed),
+            ("telegraph_time", &self.telegraph_time),
+            ("coin_spawn_interval", &self.coin_spawn_interval)
+        ];
+
+        for (name, curve) in positive_curves {
+            if !curve.is_positive() {
+                return Err(format!("{name} needs at least one point and every value above zero"));
+            }
+        }
+
+        if !is_positive(self.coin_lifetime) {
+            return Err("coin_lifetime needs to be above zero".to_string());
+        }
+
+        if let Some(kind) = self.knife_kinds.iter().find_map(|(kind, curve)| curve.0.is_empty().then_some(kind)) {
+            return Err(format!("knife_kinds has no points for {kind:?}"));
+        }
+
+        Ok(())
+    }
+
+    fn load_custom() -> Self {
+        match storage::load_ron::<DifficultyProfile>(CUSTOM_DIFFICULTY_FILE) {
+            Ok(profile) => match profile.validate() {
+                Ok(()) => profile,
+                Err(error) => {
+                    warn!("Custom difficulty file is invalid ({error}), using the normal difficulty");
+                    Self::default()
+                }
+            },
+            Err(LoadError::Missing) => {
+                let profile = Self { name: "Custom".to_string(), ..default() };
+                match storage::save_ron(CUSTOM_DIFFICULTY_FILE, &profile) {
+                    Ok(()) => info!("Wrote a custom difficulty to edit at {}", storage::data_dir().join(CUSTOM_DIFFICULTY_FILE).display()),
+                    Err(error) => warn!("Could not write custom difficulty file: {error}")
+                }
+                profile
+            },
+            Err(LoadError::Corrupt(error)) => {
+                warn!("Custom difficulty file is invalid ({error}), using the normal difficulty");
+                Self::default()
+            },
+            Err(LoadError::Io(error)) => {
+                warn!("Could not read custom difficulty file: {error}");
+                Self::default()
+            }
+        }
+    }
+}
+
+// The profile of the current run, picked when it starts so edits never change a run halfway
+#[derive(Resource, Default)]
+pub struct ActiveDifficulty {
+    pub profile: DifficultyProfile
+}
+
+#[derive(Default)]
+struct DifficultyProfileLoader;
+
+#[derive(Debug)]
+enum DifficultyProfileError {
+    Io(io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String)
+}
+
+impl fmt::Display for DifficultyProfileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DifficultyProfileError::Io(error) => write!(f, "could not read difficulty profile: {error}"),
+            DifficultyProfileError::Ron(error) => write!(f, "difficulty profile is invalid: {error}"),
+            DifficultyProfileError::Invalid(error) => write!(f, "difficulty profile is invalid: {error}")
+        }
+    }
+}
+
+impl std::error::Error for DifficultyProfileError {}
+
+impl AssetLoader for DifficultyProfileLoader {
+    type Asset = DifficultyProfile;
+    type Settings = ();
+    type Error = DifficultyProfileError;
+
+    async fn load<'a>(&'a self, reader: &'a mut Reader<'_>, _settings: &'a (), _load_context: &'a mut LoadContext<'_>) -> Result<DifficultyProfile, DifficultyProfileError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.map_err(DifficultyProfileError::Io)?;
+        let profile: DifficultyProfile = ron::de::from_bytes(&bytes).map_err(DifficultyProfileError::Ron)?;
+        profile.validate().map_err(DifficultyProfileError::Invalid)?;
+        Ok(profile)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["difficulty.ron"]
+    }
+}
+
+// Runs before anything else resets on entering the game, so timers start from the new profile
+// A replay brings the profile it was recorded with, the local files may differ
+pub fn apply_difficulty(settings: Res<RunSettings>, difficulty_assets: Res<DifficultyAssets>, profiles: Res<Assets<DifficultyProfile>>,
+    playback: Option<Res<ReplayPlayback>>, mut active_difficulty: ResMut<ActiveDifficulty>) {
+
+    if let Some(playback) = playback {
+        match playback.difficulty_profile().validate() {
+            Ok(()) => {
+                active_difficulty.profile = playback.difficulty_profile().clone();
+                return;
+            },
+            Err(error) => warn!("Difficulty of the replay is invalid ({error}), using the local {} difficulty", settings.difficulty.label())
+        }
+    }
+
+    let handle = match settings.difficulty {
+        Difficulty::Easy => &difficulty_assets.easy,
+        Difficulty::Normal => &difficulty_assets.normal,
+        Difficulty::Hard => &difficulty_assets.hard,
+        Difficulty::Custom => {
+            active_difficulty.profile = DifficultyProfile::load_custom();
+            return;
+        }
+    };
+
+    active_difficulty.profile = profiles.get(handle).cloned().unwrap_or_else(|| {
+        warn!("Difficulty {} is not loaded, using the normal difficulty", settings.difficulty.label());
+        DifficultyProfile::default()
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn curve_is_flat_before_the_first_point() {
+        let curve = Curve(vec![(2.0, 1.0), (6.0, 3.0)]);
+
+        assert_eq!(curve.at(-1), 1.0);
+        assert_eq!(curve.at(0), 1.0);
+        assert_eq!(curve.at(2), 1.0);
+    }
+
+    #[test]
+    fn curve_blends_between_points() {
+        let curve = Curve(vec![(2.0, 1.0), (6.0, 3.0), (10.0, 1.0)]);
+
+        assert_eq!(curve.at(4), 2.0);
+        assert_eq!(curve.at(6), 3.0);
+        assert_eq!(curve.at(9), 1.5);
+    }
+
+    #[test]
+    fn curve_is_flat_after_the_last_point() {
+        let curve = Curve(vec![(0.0, 0.8), (11.0, 0.25)]);
+
+        assert_eq!(curve.at(11), 0.25);
+        assert_eq!(curve.at(40), 0.25);
+    }
+
+    #[test]
+    fn curve_jumps_at_duplicate_levels() {
+        let curve = Curve(vec![(0.0, 1.0), (5.0, 2.0), (5.0, 4.0), (10.0, 6.0)]);
+
+        assert_eq!(curve.at(5), 2.0);
+        assert_eq!(curve.at(6), 4.4);
+    }
+
+    #[test]
+    fn empty_curve_is_zero() {
+        assert_eq!(Curve(Vec::new()).at(0), 0.0);
+        assert_eq!(Curve(Vec::new()).at(7), 0.0);
+    }
+
+    #[test]
+    fn default_profile_is_valid() {
+        assert!(DifficultyProfile::default().validate().is_ok());
+    }
+
+    #[test]
+    fn shipped_profiles_are_valid() {
+        let files = [
+            include_str!("../../assets/difficulty/easy.difficulty.ron"),
+            include_str!("../../assets/difficulty/normal.difficulty.ron"),
+            include_str!("../../assets/difficulty/hard.difficulty.ron")
+        ];
+
+        for file in files {
+            let profile: DifficultyProfile = ron::from_str(file).unwrap();
+            assert!(profile.validate().is_ok(), "{} is invalid", profile.name);
+        }
+    }
+
+    #[test]
+    fn profiles_without_positive_timings_are_rejected() {
+        let empty = DifficultyProfile { level_duration: Curve(Vec::new()), ..default() };
+        let negative = DifficultyProfile { knife_spawn_interval: Curve(vec![(0.0, 0.2), (10.0, -0.1)]), ..default() };
+        let zero = DifficultyProfile { coin_spawn_interval: Curve(vec![(0.0, 0.0)]), ..default() };
+
+        assert!(empty.validate().is_err());
+        assert!(negative.validate().is_err());
+        assert!(zero.validate().is_err());
+    }
+
+    #[test]
+    fn profiles_without_positive_fall_speed_are_rejected() {
+        let zero = DifficultyProfile { knife_fall_speed: Curve(vec![(0.0, 1.0), (8.0, 0.0)]), ..default() };
+        let negative = DifficultyProfile { knife_fall_speed: Curve(vec![(0.0, -1.0)]), ..default() };
+        let infinite = DifficultyProfile { knife_fall_speed: Curve(vec![(0.0, f32::INFINITY)]), ..default() };
+        let nan = DifficultyProfile { knife_fall_speed: Curve(vec![(0.0, f32::NAN)]), ..default() };
+
+        assert!(zero.validate().is_err());
+        assert!(negative.validate().is_err());
+        assert!(infinite.validate().is_err());
+        assert!(nan.validate().is_err());
+    }
+
+    #[test]
+    fn profiles_without_positive_coin_lifetime_are_rejected() {
+        for coin_lifetime in [0.0, -6.0, f32::INFINITY, f32::NAN] {
+            let profile = DifficultyProfile { coin_lifetime, ..default() };
+            assert!(profile.validate().is_err(), "coin lifetime {coin_lifetime} was accepted");
+        }
+    }
+
+    #[test]
+    fn profiles_with_empty_knife_kinds_are_rejected() {
+        let mut profile = DifficultyProfile::default();
+        profile.knife_kinds.insert(KnifeKind::Homing, Curve(Vec::new()));
+
+        assert!(profile.validate().is_err());
+    }
+}
@@ -0,0 +1 @@
+pub mod difficulty;
\ No newline at end of file
@@ -1,16 +1,23 @@
 use::bevy::prelude::*;
+use bevy::utils::HashSet;
 
-use crate::{movement::movement::Movement, terrain::terrain::Terrain, GameState};
+use crate::collision::collision::{Collider, CollisionEvent};
+use crate::{movement::movement::{Body, Movement, PhysicsSet}, terrain::terrain::Terrain, GameState, PauseState};
 
 pub struct GravityPlugin<GameState: States> {
     pub state: GameState
 }
 
 const GRAVITY_SCALE: f32 = 9.8;
+// Room for rounding and for knives turning while they fall when checking where a body was before landing
+const LANDING_TOLERANCE: f32 = 8.0;
+// Slower bounces than this stop the body instead
+const MIN_BOUNCE_SPEED: f32 = 80.0;
 
 impl Plugin for GravityPlugin<GameState> {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (apply_gravity, entity_is_touching_terrain).run_if(in_state(self.state.clone())));
+        app.add_systems(FixedUpdate, (apply_gravity.in_set(PhysicsSet::Forces), entity_is_touching_terrain.in_set(PhysicsSet::Resolve))
+            .run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
@@ -19,28 +26,149 @@ pub struct Gravity {
     pub is_touching_terrain: bool
 }
 
-fn apply_gravity(mut movement_query: Query<&mut Movement>, time: Res<Time>) {
+// Seconds a body dropped with no vertical speed takes to fall `height`, counting the time it spends at terminal velocity
+pub fn fall_time(body: &Body, height: f32) -> f32 {
+    let height = height.max(0.0);
+    let acceleration = body.mass * body.gravity_scale * GRAVITY_SCALE;
+    let speed_up_height = body.terminal_velocity * body.terminal_velocity / (2.0 * acceleration);
+
+    if height <= speed_up_height {
+        (2.0 * height / acceleration).sqrt()
+    } else {
+        body.terminal_velocity / acceleration + (height - speed_up_height) / body.terminal_velocity
+    }
+}
+
+pub fn apply_gravity(mut movement_query: Query<&mut Movement>, time: Res<Time>) {
     for mut movement in &mut movement_query {
-        movement.body.velocity.y -= movement.body.mass * GRAVITY_SCALE * time.delta_seconds();
+        let body = &mut movement.body;
+        body.velocity.y -= body.mass * body.gravity_scale * GRAVITY_SCALE * time.delta_seconds() * body.time_scale;
+        body.velocity.y = body.velocity.y.max(-body.terminal_velocity);
     }
 }
 
-fn entity_is_touching_terrain(mut movement_query: Query<(&mut Movement, &Transform, &Sprite)>, terrain_query: Query<(&Transform, &Sprite, &Terrain)>) {
+// Landing counts when the bottom was above the top of the terrain before this tick
+// The body is put back on the surface and its fall stops, or turns into a bounce when it has restitution
+pub fn entity_is_touching_terrain(mut ev_collision: EventReader<CollisionEvent>, time: Res<Time>, mut movement_query: Query<(Entity, &mut Movement, &Collider, &mut Transform)>,
+    terrain_query: Query<(&Terrain, &Collider, &Transform), Without<Movement>>) {
+
+    let mut touching = HashSet::new();
+
+    for event in ev_collision.read() {
+        for (entity, other) in event.pairs() {
+            let Ok((terrain, terrain_collider, terrain_transform)) = terrain_query.get(other) else {
+                continue;
+            };
+
+            let Ok((_, mut movement, collider, mut transform)) = movement_query.get_mut(entity) else {
+                continue;
+            };
+
+            let half_extents = collider.half_extents(&transform);
+            let distance = collider.center(&transform) - terrain_collider.center(terrain_transform);
+            let terrain_top = terrain_collider.center(terrain_transform).y + terrain_collider.half_size.y;
+            let bottom = collider.center(&transform).y - half_extents.y;
+            let previous_bottom = bottom - movement.body.velocity.y * time.delta_seconds() * movement.body.time_scale;
+
+            if movement.body.velocity.y <= 0.0 && previous_bottom >= terrain_top - LANDING_TOLERANCE {
+                transform.translation.y += terrain_top - bottom;
+
+                let bounce = -movement.body.velocity.y * movement.body.restitution;
 
-    if let Ok((terrain_transform, terrain_sprite, _terrain)) = terrain_query.get_single() {
-        let terrain_half_size = terrain_sprite.custom_size.unwrap() * terrain_transform.scale.truncate() / 2.0;
+                if bounce > MIN_BOUNCE_SPEED {
+                    movement.body.velocity.y = bounce;
+                } else {
+                    movement.body.velocity.y = 0.0;
+                    touching.insert(entity);
+                }
+                continue;
+            }
+
+            if terrain.one_way {
+                continue;
+            }
 
-        for (mut movement, transform, sprite) in movement_query.iter_mut() {
-            let entity_half_size = sprite.custom_size.unwrap() * transform.scale.truncate() / 2.0;
+            // Solid terrain pushes out along the side it was entered from the least
+            let overlap = half_extents + terrain_collider.half_size - distance.abs();
 
-            let distance = transform.translation - terrain_transform.translation;
+            if overlap.x < overlap.y {
+                transform.translation.x += overlap.x * distance.x.signum();
 
-            if distance.x.abs() < entity_half_size.x + terrain_half_size.x && 
-               distance.y.abs() < entity_half_size.y + terrain_half_size.y {
-                movement.gravity.is_touching_terrain = true;
-            } else {
-                movement.gravity.is_touching_terrain = false;
+                if movement.body.velocity.x * distance.x < 0.0 {
+                    movement.body.velocity.x = 0.0;
+                }
+            } else if distance.y < 0.0 {
+                transform.translation.y -= overlap.y;
+                movement.body.velocity.y = movement.body.velocity.y.min(0.0);
             }
         }
     }
-}
\ No newline at end of file
+
+    for (entity, mut movement, _, _) in movement_query.iter_mut() {
+        movement.gravity.is_touching_terrain = touching.contains(&entity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    const TICK: f32 = 1.0 / 60.0;
+
+    // Runs `apply_gravity` on fixed ticks and moves by the new velocity like `move_entity`, until the body fell `height`
+    fn ticked_fall_time(body: Body, height: f32) -> f32 {
+        let mut world = World::new();
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(TICK));
+        world.insert_resource(time);
+
+        let entity = world.spawn(Movement { gravity: Gravity { is_touching_terrain: false }, body }).id();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(apply_gravity);
+
+        let (mut fallen, mut elapsed) = (0.0, 0.0);
+
+        while fallen < height {
+            schedule.run(&mut world);
+            fallen -= world.get::<Movement>(entity).unwrap().body.velocity.y * TICK;
+            elapsed += TICK;
+        }
+
+        elapsed
+    }
+
+    #[test]
+    fn short_falls_never_reach_terminal_velocity() {
+        let body = Body::default();
+
+        assert_eq!(fall_time(&body, 490.0), 1.0);
+        assert_eq!(fall_time(&body, 0.0), 0.0);
+        assert_eq!(fall_time(&body, -50.0), 0.0);
+    }
+
+    #[test]
+    fn long_falls_finish_at_terminal_velocity() {
+        // Reaches 490 after half a second and 122.5 pixels, the rest takes a second at that speed
+        let body = Body { terminal_velocity: 490.0, ..default() };
+
+        assert!((fall_time(&body, 122.5) - 0.5).abs() < 1e-5);
+        assert!((fall_time(&body, 612.5) - 1.5).abs() < 1e-5);
+        assert!(fall_time(&body, 612.5) > fall_time(&Body::default(), 612.5));
+    }
+
+    #[test]
+    fn fall_time_matches_ticked_gravity() {
+        let bodies = || [Body::default(), Body { terminal_velocity: 600.0, ..default() }, Body { gravity_scale: 0.5, ..default() }];
+
+        for height in [50.0, 490.0, 700.0, 3000.0] {
+            for body in bodies() {
+                let expected = fall_time(&body, height);
+                let ticked = ticked_fall_time(body, height);
+
+                assert!((ticked - expected).abs() <= 2.0 * TICK, "fell {height} in {ticked}s, expected {expected}s");
+            }
+        }
+    }
+}
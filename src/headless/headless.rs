@@ -0,0 +1,43 @@
+use std::time::Duration;
+
+use::bevy::prelude::*;
+use bevy::time::TimeUpdateStrategy;
+
+use crate::options::options::LaunchOptions;
+use crate::{points::points::Points, stats::stats::RunStats, GameState, Level};
+
+pub struct HeadlessPlugin;
+
+// Every headless frame simulates the same amount of time, so runs go as fast as the CPU allows
+const HEADLESS_FRAME_TIME: f32 = 1.0 / 60.0;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(HEADLESS_FRAME_TIME)));
+        app.init_resource::<CompletedRuns>();
+        app.add_systems(OnEnter(GameState::Menu), start_run);
+        app.add_systems(OnEnter(GameState::GameOver), finish_run);
+    }
+}
+
+#[derive(Resource, Default)]
+struct CompletedRuns(u32);
+
+fn start_run(mut game_state: ResMut<NextState<GameState>>) {
+    game_state.set(GameState::Game);
+}
+
+fn finish_run(mut completed_runs: ResMut<CompletedRuns>, options: Res<LaunchOptions>, points: Res<Points>, level: Res<Level>, run_stats: Res<RunStats>,
+    mut game_state: ResMut<NextState<GameState>>, mut exit: EventWriter<AppExit>) {
+
+    completed_runs.0 += 1;
+
+    info!("Run {} finished: points {}, level {}, coins {}, knives dodged {}, near misses {}, time {:.1}s",
+        completed_runs.0, points.value, level.value, run_stats.coins_collected, run_stats.knives_dodged, run_stats.near_misses, run_stats.run_time);
+
+    if completed_runs.0 >= options.runs {
+        exit.send(AppExit::Success);
+    } else {
+        game_state.set(GameState::Game);
+    }
+}
@@ -0,0 +1 @@
+pub mod headless;
\ No newline at end of file
@@ -0,0 +1,84 @@
+use::bevy::prelude::*;
+
+use crate::{GameState, GameplaySet, PauseState};
+
+pub struct HealthPlugin<GameState: States> {
+    pub state: GameState
+}
+
+const INVULNERABILITY_TIME: f32 = 1.5;
+const BLINK_INTERVAL: f32 = 0.1;
+
+impl Plugin for HealthPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (tick_invulnerability, blink_while_invulnerable).chain().in_set(GameplaySet::Player).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Component)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Self { current: max, max }
+    }
+
+    pub fn take_damage(&mut self, damage: f32) {
+        self.current = (self.current - damage).max(0.0);
+    }
+
+    pub fn refill(&mut self) {
+        self.current = self.max;
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+}
+
+#[derive(Component)]
+pub struct Lives {
+    pub value: i32
+}
+
+#[derive(Component)]
+pub struct Invulnerable {
+    pub timer: Timer,
+    blink_timer: Timer
+}
+
+impl Default for Invulnerable {
+    fn default() -> Self {
+        Self {
+            timer: Timer::from_seconds(INVULNERABILITY_TIME, TimerMode::Once),
+            blink_timer: Timer::from_seconds(BLINK_INTERVAL, TimerMode::Repeating)
+        }
+    }
+}
+
+fn tick_invulnerability(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Invulnerable, &mut Visibility)>) {
+    for (entity, mut invulnerable, mut visibility) in &mut query {
+        invulnerable.timer.tick(time.delta());
+
+        if invulnerable.timer.finished() {
+            *visibility = Visibility::Inherited;
+            commands.entity(entity).remove::<Invulnerable>();
+        }
+    }
+}
+
+fn blink_while_invulnerable(time: Res<Time>, mut query: Query<(&mut Invulnerable, &mut Visibility)>) {
+    for (mut invulnerable, mut visibility) in &mut query {
+        invulnerable.blink_timer.tick(time.delta());
+
+        if invulnerable.blink_timer.just_finished() && !invulnerable.timer.finished() {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Inherited,
+                _ => Visibility::Hidden
+            };
+        }
+    }
+}
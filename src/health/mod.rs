@@ -0,0 +1 @@
+pub mod health;
\ No newline at end of file
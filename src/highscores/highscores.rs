@@ -0,0 +1,123 @@
+use::bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::options::options::is_headless;
+use crate::storage::storage::{self, LoadError};
+use crate::ui::text_input::{type_into_text_input, TextInput, TextInputSubmitEvent};
+use crate::{points::points::Points, GameState, GameplaySet, Level};
+
+pub struct HighScoresPlugin<GameState: States> {
+    pub state: GameState
+}
+
+const HIGH_SCORES_FILE: &str = "highscores.ron";
+const HIGH_SCORES_VERSION: u32 = 1;
+const MAX_HIGH_SCORES: usize = 10;
+const DEFAULT_NAME: &str = "Player";
+
+impl Plugin for HighScoresPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(HighScores::load());
+        // Only runs on the game over screen, once the run has stopped changing the points and level
+        app.add_systems(Update, save_submitted_name.after(type_into_text_input).ambiguous_with(GameplaySet::Level).ambiguous_with(GameplaySet::Score)
+            .run_if(in_state(self.state.clone())));
+        app.add_systems(OnExit(GameState::GameOver), save_unsubmitted_name.run_if(not(is_headless)));
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HighScoreEntry {
+    pub name: String,
+    pub points: i32,
+    pub level: i32
+}
+
+#[derive(Resource, Serialize, Deserialize)]
+pub struct HighScores {
+    pub version: u32,
+    pub entries: Vec<HighScoreEntry>
+}
+
+impl Default for HighScores {
+    fn default() -> Self {
+        Self { version: HIGH_SCORES_VERSION, entries: Vec::new() }
+    }
+}
+
+impl HighScores {
+    pub fn load() -> Self {
+        match storage::load_ron::<HighScores>(HIGH_SCORES_FILE) {
+            Ok(high_scores) if high_scores.version == HIGH_SCORES_VERSION => high_scores,
+            Ok(high_scores) => {
+                warn!("High score file has unsupported version {}, starting a new table", high_scores.version);
+                Self::discard_file();
+                Self::default()
+            },
+            Err(LoadError::Missing) => Self::default(),
+            Err(LoadError::Corrupt(error)) => {
+                warn!("High score file is corrupt ({error}), starting a new table");
+                Self::discard_file();
+                Self::default()
+            },
+            Err(LoadError::Io(error)) => {
+                warn!("Could not read high score file: {error}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = storage::save_ron(HIGH_SCORES_FILE, self) {
+            warn!("Could not save high score file: {error}");
+        }
+    }
+
+    pub fn qualifies(&self, points: i32) -> bool {
+        points > 0 && (self.entries.len() < MAX_HIGH_SCORES || self.entries.iter().any(|entry| points > entry.points))
+    }
+
+    pub fn insert(&mut self, entry: HighScoreEntry) {
+        self.entries.push(entry);
+        self.entries.sort_by_key(|entry| std::cmp::Reverse(entry.points));
+        self.entries.truncate(MAX_HIGH_SCORES);
+    }
+
+    fn discard_file() {
+        if let Err(error) = storage::backup_corrupt(HIGH_SCORES_FILE) {
+            warn!("Could not back up high score file: {error}");
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct HighScoreNameInput;
+
+fn record_high_score(high_scores: &mut HighScores, name: &str, points: &Points, level: &Level) {
+    let name = name.trim();
+
+    high_scores.insert(HighScoreEntry {
+        name: if name.is_empty() { DEFAULT_NAME.to_string() } else { name.to_string() },
+        points: points.value,
+        level: level.value
+    });
+    high_scores.save();
+}
+
+fn save_submitted_name(mut commands: Commands, mut ev_submit: EventReader<TextInputSubmitEvent>, mut input_query: Query<&mut Text, With<HighScoreNameInput>>,
+    mut high_scores: ResMut<HighScores>, points: Res<Points>, level: Res<Level>) {
+
+    for event in ev_submit.read() {
+        if let Ok(mut text) = input_query.get_mut(event.entity) {
+            record_high_score(&mut high_scores, &event.value, &points, &level);
+
+            text.sections[0].value = "Saved!".to_string();
+            commands.entity(event.entity).remove::<TextInput>();
+        }
+    }
+}
+
+fn save_unsubmitted_name(input_query: Query<&TextInput, With<HighScoreNameInput>>, mut high_scores: ResMut<HighScores>, points: Res<Points>, level: Res<Level>) {
+    for input in input_query.iter() {
+        record_high_score(&mut high_scores, &input.value, &points, &level);
+    }
+}
@@ -0,0 +1 @@
+pub mod highscores;
\ No newline at end of file
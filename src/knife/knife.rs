@@ -1,12 +1,21 @@
+use::bevy::ecs::system::SystemParam;
 use::bevy::prelude::*;
 use bevy_asset_loader::asset_collection::AssetCollection;
 use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl, AudioSource as KiraAudioSource};
+use serde::{Deserialize, Serialize};
 
-use crate::gravity::gravity::Gravity;
-use crate::movement::movement::{Body, Movement};
+use crate::collision::collision::{Collider, CollisionEvent, CollisionLayer, CollisionLayers};
+use crate::gravity::gravity::{apply_gravity, fall_time, Gravity};
+use crate::movement::movement::{Body, Movement, PhysicsInterpolation, PhysicsSet};
 use crate::player::player::Player;
-use crate::points::points::Points;
-use crate::{CleanupGameStateExit, GameState};
+use crate::points::points::ScoreEvent;
+use crate::settings::settings::RunSettings;
+use crate::terrain::terrain::despawn_fallen;
+use crate::{CleanupGameStateExit, GameState, GameplaySet, PauseState};
+
+use super::knife_near_miss::NearMiss;
+
+use std::f32::consts::{FRAC_PI_2, PI};
 
 pub struct KnifePlugin<GameState: States> {
     pub state: GameState
@@ -15,18 +24,39 @@ pub struct KnifePlugin<GameState: States> {
 impl Plugin for KnifePlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.add_event::<PlayerHitEvent>();
-        app.add_systems(Update, (despawn_on_terrain_touch, check_if_touch_player).run_if(in_state(self.state.clone())));
+        app.add_event::<KnifeLandedEvent>();
+        app.add_systems(Update, (despawn_on_terrain_touch, spin_knives, point_along_velocity, fade_stuck_knives).chain().before(despawn_fallen).in_set(GameplaySet::World).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(FixedUpdate, home_toward_player.before(apply_gravity).in_set(PhysicsSet::Forces).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(FixedUpdate, check_if_touch_player.in_set(PhysicsSet::Collide).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
         app.add_audio_channel::<KnifeChannel>();
     }
 }
 
 const KNIFE_SPRITE_PATH: &str = "sprites/knife.png";
+const KNIFE_MASS: f32 = 50.0;
+const HOMING_KNIFE_MASS: f32 = 25.0;
+const KNIFE_HALF_LENGTH: f32 = 28.0;
+const HOMING_ACCELERATION: f32 = 300.0;
+const HOMING_MAX_SPEED: f32 = 250.0;
+const SPIN_SPEED: f32 = 8.0;
+// Speed of the two halves thrown up and sideways by a splitting knife
+const SPLIT_VELOCITY: Vec2 = Vec2::new(220.0, 380.0);
+// Height above the landing point the halves start from, so they do not touch the terrain right away
+const SPLIT_HEIGHT: f32 = 40.0;
+// Seconds a landed knife stays in the ground when stuck knives are on, the last part of it fading out
+const STUCK_TIME: f32 = 3.0;
+const STUCK_FADE_TIME: f32 = 0.8;
+// How deep a landed knife sinks into the ground
+const STUCK_DEPTH: f32 = 14.0;
+// Stuck knives hurt less than falling ones, whatever their kind
+const STUCK_KNIFE_DAMAGE: f32 = 0.5;
 
 #[derive(Bundle)]
 struct KnifeBundle {
     knife: Knife,
     sprite: SpriteBundle,
-    movement: Movement
+    movement: Movement,
+    collider: Collider
 }
 
 #[derive(Resource)]
@@ -35,12 +65,83 @@ pub struct KnifeChannel;
 #[derive(AssetCollection, Resource)]
 pub struct KnifeAudios {
     #[asset(path = "audio/knife_hits_wood.mp3")]
-    hit_ground: Handle<KiraAudioSource>
+    pub hit_ground: Handle<KiraAudioSource>
 }
 
 #[derive(Component)]
-struct Knife {
-    pub damage: f32
+pub struct Knife {
+    pub damage: f32,
+    pub kind: KnifeKind
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, Serialize, Deserialize)]
+pub enum KnifeKind {
+    Straight,
+    Angled,
+    Homing,
+    Spinning,
+    Splitting
+}
+
+impl KnifeKind {
+    pub const ALL: [KnifeKind; 5] = [KnifeKind::Straight, KnifeKind::Angled, KnifeKind::Homing, KnifeKind::Spinning, KnifeKind::Splitting];
+
+    fn tint(&self) -> Color {
+        match self {
+            KnifeKind::Straight => Color::WHITE,
+            KnifeKind::Angled => Color::srgb(1.0, 0.85, 0.4),
+            KnifeKind::Homing => Color::srgb(1.0, 0.4, 0.4),
+            KnifeKind::Spinning => Color::srgb(0.5, 0.8, 1.0),
+            KnifeKind::Splitting => Color::srgb(0.7, 1.0, 0.5),
+        }
+    }
+
+    fn mass(&self) -> f32 {
+        match self {
+            KnifeKind::Homing => HOMING_KNIFE_MASS,
+            _ => KNIFE_MASS
+        }
+    }
+
+    // Health taken by a hit, a new kind has to pick its own
+    fn damage(&self) -> f32 {
+        match self {
+            KnifeKind::Straight => 1.0,
+            KnifeKind::Angled => 1.0,
+            KnifeKind::Homing => 1.0,
+            KnifeKind::Spinning => 2.0,
+            KnifeKind::Splitting => 1.0,
+        }
+    }
+
+    // Points for letting the knife reach the ground
+    fn score(&self) -> i32 {
+        match self {
+            KnifeKind::Straight => 1,
+            KnifeKind::Angled | KnifeKind::Splitting => 2,
+            KnifeKind::Homing | KnifeKind::Spinning => 3,
+        }
+    }
+}
+
+// A landed knife left in the ground as a hazard, it is harmless once it starts fading
+#[derive(Component)]
+pub struct Stuck {
+    timer: Timer
+}
+
+impl Stuck {
+    fn is_fading(&self) -> bool {
+        self.timer.remaining_secs() < STUCK_FADE_TIME
+    }
+}
+
+#[derive(Component)]
+struct Homing;
+
+#[derive(Component)]
+struct Spin {
+    speed: f32
 }
 
 #[derive(Event)]
@@ -48,20 +149,29 @@ pub struct PlayerHitEvent {
     pub damage: f32
 }
 
-pub fn spawn_knife(mut commands: Commands, asset_server: Res<AssetServer>, spawn_position: Vec3) {
+// `near_miss` is set when the knife passed close to the player on the way down
+#[derive(Event)]
+pub struct KnifeLandedEvent {
+    pub position: Vec3,
+    pub near_miss: bool
+}
+
+// `fall_speed` multiplies the gravity on the knife
+pub fn spawn_knife(commands: &mut Commands, asset_server: &AssetServer, spawn_position: Vec3, kind: KnifeKind, velocity: Vec2, fall_speed: f32) {
 
-    commands.spawn( KnifeBundle {
-        knife: Knife { damage: 1.0 },
+    let mut knife = commands.spawn( KnifeBundle {
+        knife: Knife { damage: kind.damage(), kind },
         sprite: SpriteBundle {
             texture: asset_server.load(KNIFE_SPRITE_PATH),
             sprite: Sprite {
+                color: kind.tint(),
                 custom_size: Some(Vec2::new(16.0, 16.0)),
                 ..default()
             },
             transform: Transform {
                 scale: Vec3::splat(4.0),
                 translation: spawn_position,
-                rotation: Quat::from_rotation_z(std::f32::consts::PI),
+                rotation: Quat::from_rotation_z(PI),
                 ..default()
             },
             ..default()
@@ -71,40 +181,137 @@ pub fn spawn_knife(mut commands: Commands, asset_server: Res<AssetServer>, spawn
                 is_touching_terrain: false
             },
             body: Body {
-                mass: 50.0,
-                velocity: Vec2::ZERO,
+                mass: kind.mass(),
+                velocity,
+                gravity_scale: fall_speed,
                 ..default()
             }
-        }
-    }).insert(CleanupGameStateExit);
+        },
+        collider: Collider::new(Vec2::new(8.0, KNIFE_HALF_LENGTH), CollisionLayers::new(CollisionLayer::KNIFE, CollisionLayer::PLAYER | CollisionLayer::TERRAIN)).rotating()
+    });
+
+    match kind {
+        KnifeKind::Homing => { knife.insert(Homing); },
+        KnifeKind::Spinning => { knife.insert(Spin { speed: SPIN_SPEED }); },
+        _ => {}
+    }
+
+    knife.insert(CleanupGameStateExit);
 }
 
-fn despawn_on_terrain_touch(mut commands: Commands, knife_query: Query<(Entity, &Movement), With<Knife>>, mut points: ResMut<Points>,
-knife_audios: Res<KnifeAudios>, knife_channel: Res<AudioChannel<KnifeChannel>>) {
-    for (knife, &ref movement) in knife_query.iter() {
+// Where a knife dropped from `spawn_position` reaches the ground, homing knives are expected to land where they start
+pub fn landing_x(kind: KnifeKind, spawn_position: Vec3, velocity: Vec2, fall_speed: f32, ground_height: f32) -> f32 {
+    let drop = spawn_position.y - KNIFE_HALF_LENGTH - ground_height;
+    let body = Body { mass: kind.mass(), velocity, gravity_scale: fall_speed, ..default() };
+
+    spawn_position.x + velocity.x * fall_time(&body, drop)
+}
+
+#[derive(SystemParam)]
+pub struct LandingSound<'w> {
+    knife_audios: Res<'w, KnifeAudios>,
+    knife_channel: Res<'w, AudioChannel<KnifeChannel>>
+}
+
+impl LandingSound<'_> {
+    fn play(&self) {
+        self.knife_channel.play(self.knife_audios.hit_ground.clone()).with_volume(0.2);
+    }
+}
+
+pub fn despawn_on_terrain_touch(mut commands: Commands, mut knife_query: Query<(Entity, &Knife, &Movement, &mut Transform, Has<NearMiss>)>, mut ev_score: EventWriter<ScoreEvent>, asset_server: Res<AssetServer>,
+landing_sound: LandingSound, mut ev_knife_landed: EventWriter<KnifeLandedEvent>, settings: Res<RunSettings>) {
+    for (entity, knife, movement, mut transform, near_miss) in knife_query.iter_mut() {
         if movement.gravity.is_touching_terrain {
-            knife_channel.play(knife_audios.hit_ground.clone()).with_volume(0.2);
-            commands.entity(knife).despawn();
-            points.value += 1;
+            landing_sound.play();
+            ev_score.send(ScoreEvent::new(knife.kind.score()));
+            ev_knife_landed.send(KnifeLandedEvent { position: transform.translation, near_miss });
+
+            if knife.kind == KnifeKind::Splitting {
+                commands.entity(entity).despawn();
+                let position = transform.translation + Vec3::Y * SPLIT_HEIGHT;
+
+                for side in [-1.0, 1.0] {
+                    spawn_knife(&mut commands, &asset_server, position, KnifeKind::Angled, SPLIT_VELOCITY * Vec2::new(side, 1.0), movement.body.gravity_scale);
+                }
+            } else if settings.stuck_knives {
+                // Without movement the knife stays where it landed
+                transform.translation.y -= STUCK_DEPTH;
+                commands.entity(entity)
+                    .remove::<(Movement, PhysicsInterpolation, Homing, Spin)>()
+                    .insert(Stuck { timer: Timer::from_seconds(STUCK_TIME, TimerMode::Once) });
+            } else {
+                commands.entity(entity).despawn();
+            }
+        }
+    }
+}
+
+fn fade_stuck_knives(mut commands: Commands, time: Res<Time>, mut knife_query: Query<(Entity, &Knife, &mut Stuck, &mut Sprite)>) {
+    for (entity, knife, mut stuck, mut sprite) in &mut knife_query {
+        stuck.timer.tick(time.delta());
+
+        if stuck.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        if stuck.is_fading() {
+            sprite.color = knife.kind.tint().with_alpha(stuck.timer.remaining_secs() / STUCK_FADE_TIME);
         }
     }
 }
 
-fn check_if_touch_player(mut commands: Commands, mut ev_player_touched: EventWriter<PlayerHitEvent>, knife_query: Query<(&Movement, &Transform, &Sprite, &Knife, Entity)>, player_query: Query<(&Transform, &Sprite, &Player)>) {
+fn home_toward_player(time: Res<Time>, mut knife_query: Query<(&mut Movement, &Transform), With<Homing>>, player_query: Query<&Transform, With<Player>>) {
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
 
-    if let Ok((player_transform, player_sprite, _player)) = player_query.get_single() {
-        let player_half_size = player_sprite.custom_size.unwrap() * player_transform.scale.truncate() / 2.0;
+    for (mut movement, transform) in &mut knife_query {
+        let towards_player = (player_transform.translation.x - transform.translation.x).signum();
+        let velocity_x = movement.body.velocity.x + towards_player * HOMING_ACCELERATION * time.delta_seconds() * movement.body.time_scale;
 
-        for (_movement, transform, sprite, knife, entity) in knife_query.iter() {
-            let knife_half_size = sprite.custom_size.unwrap() * transform.scale.truncate() / 2.0;
-            
-            let distance = transform.translation - player_transform.translation;
+        movement.body.velocity.x = velocity_x.clamp(-HOMING_MAX_SPEED, HOMING_MAX_SPEED);
+    }
+}
+
+fn spin_knives(time: Res<Time>, mut query: Query<(&Spin, &Movement, &mut Transform)>) {
+    for (spin, movement, mut transform) in &mut query {
+        transform.rotate_z(spin.speed * time.delta_seconds() * movement.body.time_scale);
+    }
+}
 
-            if distance.x.abs() < knife_half_size.x + player_half_size.x &&
-                       distance.y.abs() < knife_half_size.y + player_half_size.y {
-                        ev_player_touched.send(PlayerHitEvent { damage: knife.damage });
-                        commands.entity(entity).despawn();
+// Spinning knives turn on their own
+type UnspunKnives = (With<Knife>, Without<Spin>);
+
+// The blade points where the knife is going, the sprite points up when not rotated
+fn point_along_velocity(mut query: Query<(&Movement, &mut Transform), UnspunKnives>) {
+    for (movement, mut transform) in &mut query {
+        if movement.body.velocity != Vec2::ZERO {
+            let angle = movement.body.velocity.y.atan2(movement.body.velocity.x) - FRAC_PI_2;
+            transform.rotation = Quat::from_rotation_z(angle);
+        }
+    }
+}
+
+// Falling knives break on the player, stuck ones stay and hurt again once the invulnerability is over
+fn check_if_touch_player(mut commands: Commands, mut ev_collision: EventReader<CollisionEvent>, mut ev_player_touched: EventWriter<PlayerHitEvent>, knife_query: Query<(&Knife, Option<&Stuck>)>,
+    player_query: Query<(), With<Player>>) {
+
+    for event in ev_collision.read() {
+        for (entity, other) in event.pairs() {
+            if let Ok((knife, stuck)) = knife_query.get(entity) {
+                if player_query.contains(other) {
+                    match stuck {
+                        Some(stuck) if stuck.is_fading() => {},
+                        Some(_) => { ev_player_touched.send(PlayerHitEvent { damage: STUCK_KNIFE_DAMAGE }); },
+                        None => {
+                            ev_player_touched.send(PlayerHitEvent { damage: knife.damage });
+                            commands.entity(entity).despawn();
+                        }
                     }
+                }
+            }
         }
     }
-}
\ No newline at end of file
+}
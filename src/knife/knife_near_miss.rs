@@ -0,0 +1,103 @@
+use::bevy::prelude::*;
+use bevy_kira_audio::{AudioApp, AudioChannel, AudioControl};
+
+use crate::collision::collision::Collider;
+use crate::movement::movement::PhysicsSet;
+use crate::player::player::Player;
+use crate::points::points::ScoreEvent;
+use crate::{GameState, GameplaySet, PauseState};
+
+use super::knife::{despawn_on_terrain_touch, Knife, KnifeAudios, KnifeLandedEvent, Stuck};
+
+pub struct NearMissPlugin<GameState: States> {
+    pub state: GameState
+}
+
+// How far around the player's hitbox a knife counts as a near miss
+const NEAR_MISS_MARGIN: f32 = 40.0;
+const NEAR_MISS_BONUS: i32 = 15;
+// The whole game runs this fast for a moment after a near miss
+const FLASH_TIME_SCALE: f32 = 0.35;
+// Real seconds the slow motion flash lasts
+const FLASH_TIME: f32 = 0.25;
+
+impl Plugin for NearMissPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_event::<NearMissEvent>();
+        app.add_audio_channel::<NearMissChannel>();
+        app.init_resource::<NearMissFlash>();
+        app.add_systems(OnExit(GameState::Game), end_flash);
+        app.add_systems(FixedUpdate, mark_near_misses.in_set(PhysicsSet::Collide).run_if(in_state(self.state)).run_if(in_state(PauseState::Running)));
+        app.add_systems(Update, (reward_near_misses, update_flash).chain().after(despawn_on_terrain_touch).in_set(GameplaySet::World).run_if(in_state(self.state)).run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Resource)]
+pub struct NearMissChannel;
+
+// The knife came close to the player, it only counts once it lands without hitting
+#[derive(Component)]
+pub struct NearMiss;
+
+#[derive(Event)]
+pub struct NearMissEvent;
+
+#[derive(Resource, Default)]
+struct NearMissFlash {
+    timer: Option<Timer>
+}
+
+// Knives still falling that have not come close yet
+type UnmarkedKnives = (With<Knife>, Without<NearMiss>, Without<Stuck>);
+
+fn mark_near_misses(mut commands: Commands, knife_query: Query<(Entity, &Collider, &Transform), UnmarkedKnives>,
+    player_query: Query<(&Collider, &Transform), With<Player>>) {
+
+    let Ok((player_collider, player_transform)) = player_query.get_single() else {
+        return;
+    };
+
+    let zone = Collider { half_size: player_collider.half_size + Vec2::splat(NEAR_MISS_MARGIN), ..*player_collider };
+
+    for (entity, collider, transform) in &knife_query {
+        if collider.overlaps(transform, &zone, player_transform) {
+            commands.entity(entity).insert(NearMiss);
+        }
+    }
+}
+
+fn reward_near_misses(mut ev_knife_landed: EventReader<KnifeLandedEvent>, mut ev_near_miss: EventWriter<NearMissEvent>, mut ev_score: EventWriter<ScoreEvent>,
+    mut flash: ResMut<NearMissFlash>, mut virtual_time: ResMut<Time<Virtual>>, knife_audios: Res<KnifeAudios>, near_miss_channel: Res<AudioChannel<NearMissChannel>>) {
+
+    for event in ev_knife_landed.read() {
+        if !event.near_miss {
+            continue;
+        }
+
+        ev_near_miss.send(NearMissEvent);
+        ev_score.send(ScoreEvent::combo_at(NEAR_MISS_BONUS, event.position));
+        near_miss_channel.play(knife_audios.hit_ground.clone()).with_volume(0.25).with_playback_rate(1.8);
+
+        flash.timer = Some(Timer::from_seconds(FLASH_TIME, TimerMode::Once));
+        virtual_time.set_relative_speed(FLASH_TIME_SCALE);
+    }
+}
+
+// Counted in real time, virtual time is the one slowed down
+fn update_flash(real_time: Res<Time<Real>>, mut flash: ResMut<NearMissFlash>, mut virtual_time: ResMut<Time<Virtual>>) {
+    let Some(timer) = &mut flash.timer else {
+        return;
+    };
+
+    timer.tick(real_time.delta());
+
+    if timer.finished() {
+        flash.timer = None;
+        virtual_time.set_relative_speed(1.0);
+    }
+}
+
+fn end_flash(mut flash: ResMut<NearMissFlash>, mut virtual_time: ResMut<Time<Virtual>>) {
+    flash.timer = None;
+    virtual_time.set_relative_speed(1.0);
+}
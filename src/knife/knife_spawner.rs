@@ -1,62 +1,173 @@
+use std::time::Duration;
+
+use::bevy::ecs::system::SystemParam;
 use::bevy::prelude::*;
-use bevy::window::PrimaryWindow;
+use rand::distributions::{Distribution, WeightedIndex};
 use rand::Rng;
 
-use crate::{GameState, LevelUpEvent};
+use crate::difficulty::difficulty::{apply_difficulty, ActiveDifficulty, DifficultyProfile};
+use crate::random::random::{GameRng, RngStream};
+use crate::settings::settings::RunSettings;
+use crate::terrain::terrain::ActiveArena;
+use crate::{CleanupGameStateExit, GameState, GameplaySet, Level, LevelUpEvent, PauseState, PlayField};
 
-use super::knife::spawn_knife;
+use super::knife::{landing_x, spawn_knife, KnifeKind};
+use super::knife_waves::{direct_waves, WaveDirector};
 
 pub struct KnifeSpawnerPlugin<GameState: States> {
     pub state: GameState
 }
 
-const KNIFE_SPAWN_TIMER: f32 = 0.2;
-const MIN_KNIFE_SPAWN_TIMER: f32 = 0.05;
+const MIN_ANGLED_THROW_SPEED: f32 = 150.0;
+const MAX_ANGLED_THROW_SPEED: f32 = 300.0;
+const TELEGRAPH_SIZE: Vec2 = Vec2::new(40.0, 8.0);
+const TELEGRAPH_COLOR: Color = Color::srgba(0.9, 0.1, 0.1, 0.8);
 
 impl Plugin for KnifeSpawnerPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.init_resource::<KnifeSpawnTimer>();
-        app.add_systems(Update, (tick_knife_spawn_timer, spawn_knife_over_time, listen_for_level_up).run_if(in_state(self.state.clone())));
+        app.add_systems(OnEnter(GameState::Game), reset_knife_spawn_timer.after(apply_difficulty));
+        // Waves go first, so the spawn timer knows in the same frame whether a wave has taken over
+        app.add_systems(Update, ((listen_for_level_up, tick_knife_spawn_timer, spawn_knife_over_time).chain().after(direct_waves), update_telegraphs).in_set(GameplaySet::Spawn)
+            .run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
 #[derive(Resource)]
 pub struct KnifeSpawnTimer {
-    pub timer: Timer,
-    pub current_spawn_timer: f32,
-    pub min_spawn_cd: f32,
+    pub timer: Timer
+}
+
+// Marks where a knife is about to land, the knife is spawned when the timer runs out
+#[derive(Component)]
+struct KnifeTelegraph {
+    timer: Timer,
+    kind: KnifeKind,
+    spawn_position: Vec3,
+    velocity: Vec2,
+    fall_speed: f32
 }
 
 impl Default for KnifeSpawnTimer {
     fn default() -> KnifeSpawnTimer {
-        KnifeSpawnTimer { 
-            timer: Timer::from_seconds(KNIFE_SPAWN_TIMER, TimerMode::Repeating),
-            current_spawn_timer: KNIFE_SPAWN_TIMER,
-            min_spawn_cd: MIN_KNIFE_SPAWN_TIMER
-        }
+        KnifeSpawnTimer::for_level(&DifficultyProfile::default(), 0)
     }
 }
 
+impl KnifeSpawnTimer {
+    fn for_level(profile: &DifficultyProfile, level: i32) -> Self {
+        Self { timer: Timer::from_seconds(profile.knife_spawn_interval.at(level), TimerMode::Repeating) }
+    }
+}
+
+fn reset_knife_spawn_timer(mut knife_spawn_timer: ResMut<KnifeSpawnTimer>, difficulty: Res<ActiveDifficulty>) {
+    *knife_spawn_timer = KnifeSpawnTimer::for_level(&difficulty.profile, 0);
+}
+
 fn tick_knife_spawn_timer(mut knife_spawn_timer: ResMut<KnifeSpawnTimer>, time: Res<Time>) {
     knife_spawn_timer.timer.tick(time.delta());
 }
 
-fn spawn_knife_over_time(commands: Commands, window_query: Query<&Window, With<PrimaryWindow>>, knife_spawn_timer: Res<KnifeSpawnTimer>, asset_server: Res<AssetServer>) {
-    if knife_spawn_timer.timer.finished() {
-        let window = window_query.get_single().unwrap();
+fn spawn_knife_over_time(mut launcher: KnifeLauncher, mut game_rng: ResMut<GameRng>, knife_spawn_timer: Res<KnifeSpawnTimer>, wave_director: Res<WaveDirector>) {
+    // Waves take over the spawning while they run
+    if knife_spawn_timer.timer.finished() && !wave_director.is_running() {
+        let rng = game_rng.stream(RngStream::Knives);
+        let random_x = rng.gen_range((-launcher.play_field.width/2.0)+16.0..(launcher.play_field.width/2.0)-16.0);
+        let kind = choose_knife_kind(rng, &launcher.difficulty.profile, launcher.level.value);
+        let velocity = initial_velocity(kind, random_x, rng);
+
+        launcher.launch(kind, random_x, velocity);
+    }
+}
+
+// Angled knives are thrown toward the middle so they stay on screen
+pub fn initial_velocity(kind: KnifeKind, x: f32, rng: &mut impl Rng) -> Vec2 {
+    match kind {
+        KnifeKind::Angled => Vec2::new(-x.signum() * rng.gen_range(MIN_ANGLED_THROW_SPEED..MAX_ANGLED_THROW_SPEED), 0.0),
+        _ => Vec2::ZERO
+    }
+}
+
+// Everything needed to drop a knife, shared by the spawn timer and the waves
+#[derive(SystemParam)]
+pub struct KnifeLauncher<'w, 's> {
+    commands: Commands<'w, 's>,
+    asset_server: Res<'w, AssetServer>,
+    pub play_field: Res<'w, PlayField>,
+    pub level: Res<'w, Level>,
+    pub difficulty: Res<'w, ActiveDifficulty>,
+    settings: Res<'w, RunSettings>,
+    arena: Res<'w, ActiveArena>
+}
+
+impl KnifeLauncher<'_, '_> {
+    // Drops a knife from the top edge at `x`, after a warning marker unless expert mode is on
+    pub fn launch(&mut self, kind: KnifeKind, x: f32, velocity: Vec2) {
+        let spawn_position = Vec3::new(x, (self.play_field.height / 2.0) - 16.0, 0.0);
+        let fall_speed = self.difficulty.profile.knife_fall_speed.at(self.level.value);
+
+        if self.settings.expert_mode {
+            spawn_knife(&mut self.commands, &self.asset_server, spawn_position, kind, velocity, fall_speed);
+            return;
+        }
 
-        let random_x = rand::thread_rng().gen_range((-window.width()/2.0)+16.0..(window.width()/2.0)-16.0);
-        let spawn_y = (window.height() / 2.0) - 16.0;
+        // Thrown knives can land on another platform than the one below their start, so the landing is checked again there
+        let bottom = -self.play_field.height / 2.0;
+        let surface_at = |x: f32| self.arena.surface_below(x, spawn_position.y).unwrap_or(bottom);
+        let first_guess = landing_x(kind, spawn_position, velocity, fall_speed, surface_at(spawn_position.x));
+        let ground = surface_at(first_guess);
+        let marker_x = landing_x(kind, spawn_position, velocity, fall_speed, ground).clamp(-self.play_field.width / 2.0, self.play_field.width / 2.0);
 
-        spawn_knife(commands, asset_server, Vec3::new(random_x, spawn_y, 0.0));
+        self.commands.spawn((SpriteBundle {
+            sprite: Sprite {
+                color: TELEGRAPH_COLOR.with_alpha(0.0),
+                custom_size: Some(TELEGRAPH_SIZE),
+                ..default()
+            },
+            transform: Transform::from_xyz(marker_x, ground + TELEGRAPH_SIZE.y / 2.0, 1.0),
+            ..default()
+        }, KnifeTelegraph {
+            timer: Timer::from_seconds(self.difficulty.profile.telegraph_time.at(self.level.value), TimerMode::Once),
+            kind,
+            spawn_position,
+            velocity,
+            fall_speed
+        }, CleanupGameStateExit));
     }
 }
 
-fn listen_for_level_up(mut level_up_event_reader: EventReader<LevelUpEvent>, mut knife_spawn_timer: ResMut<KnifeSpawnTimer>) {
-    for _ in level_up_event_reader.read() {    
-        if knife_spawn_timer.current_spawn_timer - 0.01 >= knife_spawn_timer.min_spawn_cd {
-            knife_spawn_timer.current_spawn_timer -= 0.01;
-            knife_spawn_timer.timer = Timer::from_seconds(knife_spawn_timer.current_spawn_timer, TimerMode::Repeating);
+// The marker grows and fades in while the knife is on its way
+fn update_telegraphs(mut commands: Commands, time: Res<Time>, asset_server: Res<AssetServer>,
+    mut telegraph_query: Query<(Entity, &mut KnifeTelegraph, &mut Sprite, &mut Transform)>) {
+
+    for (entity, mut telegraph, mut sprite, mut transform) in &mut telegraph_query {
+        telegraph.timer.tick(time.delta());
+
+        if telegraph.timer.finished() {
+            spawn_knife(&mut commands, &asset_server, telegraph.spawn_position, telegraph.kind, telegraph.velocity, telegraph.fall_speed);
+            commands.entity(entity).despawn();
+            continue;
         }
+
+        let progress = telegraph.timer.fraction();
+        sprite.color = TELEGRAPH_COLOR.with_alpha(TELEGRAPH_COLOR.alpha() * progress);
+        transform.scale = Vec3::new(0.4 + 0.6 * progress, 1.0, 1.0);
+    }
+}
+
+fn choose_knife_kind(rng: &mut impl Rng, profile: &DifficultyProfile, level: i32) -> KnifeKind {
+    let weights = KnifeKind::ALL.map(|kind| profile.knife_kind_weight(kind, level));
+
+    match WeightedIndex::new(weights) {
+        Ok(index) => KnifeKind::ALL[index.sample(rng)],
+        Err(_) => KnifeKind::Straight
+    }
+}
+
+// Keeps the time already passed, so a shorter interval does not restart the wait for the next knife
+fn listen_for_level_up(mut level_up_event_reader: EventReader<LevelUpEvent>, mut knife_spawn_timer: ResMut<KnifeSpawnTimer>, level: Res<Level>, difficulty: Res<ActiveDifficulty>) {
+    for _ in level_up_event_reader.read() {
+        let interval = difficulty.profile.knife_spawn_interval.at(level.value);
+        knife_spawn_timer.timer.set_duration(Duration::from_secs_f32(interval));
     }
 }
\ No newline at end of file
@@ -0,0 +1,325 @@
+use std::fmt;
+use std::io;
+
+use::bevy::asset::io::Reader;
+use::bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
+use::bevy::prelude::*;
+use bevy_asset_loader::asset_collection::AssetCollection;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::random::random::{GameRng, RngStream};
+use crate::{GameState, GameplaySet, PauseState};
+
+use super::knife::KnifeKind;
+use super::knife_spawner::{initial_velocity, KnifeLauncher};
+
+pub struct KnifeWavesPlugin<GameState: States> {
+    pub state: GameState
+}
+
+const FIRST_WAVE_DELAY: f32 = 10.0;
+const WAVE_INTERVAL: f32 = 12.0;
+// Knife positions in patterns go from -1 (left edge) to 1 (right edge), this keeps them inside the screen
+const EDGE_MARGIN: f32 = 16.0;
+
+impl Plugin for KnifeWavesPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<WavePatterns>();
+        app.init_asset_loader::<WavePatternsLoader>();
+        app.init_resource::<WaveDirector>();
+        app.add_systems(OnEnter(GameState::Game), reset_wave_director);
+        app.add_systems(Update, direct_waves.in_set(GameplaySet::Spawn).run_if(in_state(self.state)).run_if(in_state(PauseState::Running)));
+    }
+}
+
+// Loaded with the other assets before the menu, so the first wave of every run starts at the same time
+#[derive(AssetCollection, Resource)]
+pub struct WaveAssets {
+    #[asset(path = "waves/knife_patterns.waves.ron")]
+    patterns: Handle<WavePatterns>
+}
+
+// All patterns the director can choose from, loaded from a RON file in the assets folder
+#[derive(Asset, TypePath, Deserialize)]
+pub struct WavePatterns {
+    pub patterns: Vec<WavePattern>
+}
+
+// `weight` is the chance of being picked relative to the other patterns available on the current level
+#[derive(Deserialize)]
+pub struct WavePattern {
+    pub name: String,
+    pub min_level: i32,
+    #[serde(default)]
+    pub max_level: Option<i32>,
+    pub weight: f32,
+    pub steps: Vec<WaveStep>
+}
+
+// `time` is in seconds from the start of the wave
+#[derive(Deserialize)]
+pub struct WaveStep {
+    pub time: f32,
+    pub kind: KnifeKind,
+    pub shape: WaveShape
+}
+
+#[derive(Deserialize)]
+pub enum WaveShape {
+    // One knife
+    At(f32),
+    // Knives spread evenly over the whole width at once, leaving `gap` neighbouring slots free at a random spot
+    Curtain { count: u32, gap: u32 },
+    // Knives one after another from `from` to `to`, spread over `duration` seconds
+    Sweep { from: f32, to: f32, count: u32, duration: f32 },
+    // Knives at random spots spread over `duration` seconds
+    Rain { count: u32, duration: f32 }
+}
+
+impl WavePatterns {
+    // Patterns are edited by hand, a wave needs knives to drop and times the RNG can pick from
+    fn validate(&self) -> Result<(), String> {
+        for pattern in &self.patterns {
+            for step in &pattern.steps {
+                let (count, duration) = match step.shape {
+                    WaveShape::At(_) => (1, 0.0),
+                    WaveShape::Curtain { count, .. } => (count, 0.0),
+                    WaveShape::Sweep { count, duration, .. } | WaveShape::Rain { count, duration } => (count, duration)
+                };
+
+                if !step.time.is_finite() || step.time < 0.0 {
+                    return Err(format!("\"{}\" has a step at {} seconds, times can not be negative", pattern.name, step.time));
+                }
+
+                if count == 0 {
+                    return Err(format!("\"{}\" has a step without knives", pattern.name));
+                }
+
+                if !duration.is_finite() || duration < 0.0 {
+                    return Err(format!("\"{}\" has a step lasting {duration} seconds, durations can not be negative", pattern.name));
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl WavePattern {
+    fn fits_level(&self, level: i32) -> bool {
+        level >= self.min_level && self.max_level.is_none_or(|max_level| level <= max_level)
+    }
+
+    // Turns the steps into single knives sorted by the time they drop, positions are still from -1 to 1
+    fn schedule(&self, rng: &mut impl Rng) -> Vec<ScheduledKnife> {
+        let mut knives = Vec::new();
+
+        for step in &self.steps {
+            let mut add = |time: f32, x: f32| knives.push(ScheduledKnife { time: step.time + time, x, kind: step.kind });
+
+            match step.shape {
+                WaveShape::At(x) => add(0.0, x),
+                WaveShape::Curtain { count, gap } => {
+                    let gap_start = rng.gen_range(0..=count.saturating_sub(gap));
+
+                    for slot in (0..count).filter(|slot| !(gap_start..gap_start + gap).contains(slot)) {
+                        add(0.0, spread(slot, count, -1.0, 1.0));
+                    }
+                },
+                WaveShape::Sweep { from, to, count, duration } => {
+                    for index in 0..count {
+                        add(spread(index, count, 0.0, duration), spread(index, count, from, to));
+                    }
+                },
+                WaveShape::Rain { count, duration } => {
+                    for _ in 0..count {
+                        add(rng.gen_range(0.0..=duration), rng.gen_range(-1.0..=1.0));
+                    }
+                }
+            }
+        }
+
+        // Latest first, so the next knife is always popped off the end
+        knives.sort_by(|a, b| b.time.total_cmp(&a.time));
+        knives
+    }
+}
+
+// Value of the `index`th of `count` evenly spaced points from `from` to `to`
+fn spread(index: u32, count: u32, from: f32, to: f32) -> f32 {
+    if count <= 1 {
+        return (from + to) / 2.0;
+    }
+
+    from + (to - from) * index as f32 / (count - 1) as f32
+}
+
+struct ScheduledKnife {
+    time: f32,
+    x: f32,
+    kind: KnifeKind
+}
+
+struct RunningWave {
+    elapsed: f32,
+    knives: Vec<ScheduledKnife>
+}
+
+#[derive(Resource)]
+pub struct WaveDirector {
+    cooldown: Timer,
+    running: Option<RunningWave>
+}
+
+impl Default for WaveDirector {
+    fn default() -> Self {
+        Self {
+            cooldown: Timer::from_seconds(FIRST_WAVE_DELAY, TimerMode::Once),
+            running: None
+        }
+    }
+}
+
+impl WaveDirector {
+    pub fn is_running(&self) -> bool {
+        self.running.is_some()
+    }
+}
+
+#[derive(Default)]
+struct WavePatternsLoader;
+
+#[derive(Debug)]
+enum WavePatternsError {
+    Io(io::Error),
+    Ron(ron::error::SpannedError),
+    Invalid(String)
+}
+
+impl fmt::Display for WavePatternsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WavePatternsError::Io(error) => write!(f, "could not read wave patterns: {error}"),
+            WavePatternsError::Ron(error) => write!(f, "wave patterns are invalid: {error}"),
+            WavePatternsError::Invalid(error) => write!(f, "wave patterns are invalid: {error}")
+        }
+    }
+}
+
+impl std::error::Error for WavePatternsError {}
+
+impl AssetLoader for WavePatternsLoader {
+    type Asset = WavePatterns;
+    type Settings = ();
+    type Error = WavePatternsError;
+
+    async fn load<'a>(&'a self, reader: &'a mut Reader<'_>, _settings: &'a (), _load_context: &'a mut LoadContext<'_>) -> Result<WavePatterns, WavePatternsError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.map_err(WavePatternsError::Io)?;
+        let wave_patterns: WavePatterns = ron::de::from_bytes(&bytes).map_err(WavePatternsError::Ron)?;
+        wave_patterns.validate().map_err(WavePatternsError::Invalid)?;
+        Ok(wave_patterns)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["waves.ron"]
+    }
+}
+
+fn reset_wave_director(mut director: ResMut<WaveDirector>) {
+    director.cooldown = Timer::from_seconds(FIRST_WAVE_DELAY, TimerMode::Once);
+    director.running = None;
+}
+
+pub fn direct_waves(time: Res<Time>, mut director: ResMut<WaveDirector>, wave_assets: Res<WaveAssets>, wave_patterns: Res<Assets<WavePatterns>>,
+    mut game_rng: ResMut<GameRng>, mut launcher: KnifeLauncher) {
+
+    let half_width = launcher.play_field.width / 2.0 - EDGE_MARGIN;
+    let director = &mut *director;
+
+    if let Some(wave) = &mut director.running {
+        wave.elapsed += time.delta_seconds();
+
+        while wave.knives.last().is_some_and(|knife| knife.time <= wave.elapsed) {
+            let Some(knife) = wave.knives.pop() else {
+                break;
+            };
+
+            let x = knife.x.clamp(-1.0, 1.0) * half_width;
+            let velocity = initial_velocity(knife.kind, x, game_rng.stream(RngStream::Waves));
+            launcher.launch(knife.kind, x, velocity);
+        }
+
+        if wave.knives.is_empty() {
+            director.running = None;
+            director.cooldown = Timer::from_seconds(WAVE_INTERVAL, TimerMode::Once);
+        }
+        return;
+    }
+
+    director.cooldown.tick(time.delta());
+
+    if !director.cooldown.finished() {
+        return;
+    }
+
+    let Some(wave_patterns) = wave_patterns.get(&wave_assets.patterns) else {
+        return;
+    };
+
+    let level = launcher.level.value;
+    let candidates: Vec<&WavePattern> = wave_patterns.patterns.iter().filter(|pattern| pattern.fits_level(level)).collect();
+    let rng = game_rng.stream(RngStream::Waves);
+
+    let Ok(index) = WeightedIndex::new(candidates.iter().map(|pattern| pattern.weight)) else {
+        return;
+    };
+
+    let pattern = candidates[index.sample(rng)];
+    info!("Starting knife wave \"{}\" on level {}", pattern.name, level);
+
+    director.running = Some(RunningWave {
+        elapsed: 0.0,
+        knives: pattern.schedule(rng)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rain(count: u32, duration: f32) -> WavePatterns {
+        WavePatterns {
+            patterns: vec![WavePattern {
+                name: "Rain".to_string(),
+                min_level: 0,
+                max_level: None,
+                weight: 1.0,
+                steps: vec![WaveStep { time: 0.0, kind: KnifeKind::Straight, shape: WaveShape::Rain { count, duration } }]
+            }]
+        }
+    }
+
+    #[test]
+    fn shipped_patterns_are_valid() {
+        let wave_patterns: WavePatterns = ron::from_str(include_str!("../../assets/waves/knife_patterns.waves.ron")).unwrap();
+
+        assert!(wave_patterns.validate().is_ok());
+    }
+
+    #[test]
+    fn patterns_with_unusable_durations_are_rejected() {
+        assert!(rain(5, 0.0).validate().is_ok());
+
+        for duration in [-1.0, f32::NAN, f32::INFINITY] {
+            assert!(rain(5, duration).validate().is_err(), "duration {duration} was accepted");
+        }
+    }
+
+    #[test]
+    fn patterns_without_knives_are_rejected() {
+        assert!(rain(0, 2.0).validate().is_err());
+    }
+}
\ No newline at end of file
@@ -1,2 +1,4 @@
 pub mod knife;
-pub mod knife_spawner;
\ No newline at end of file
+pub mod knife_near_miss;
+pub mod knife_spawner;
+pub mod knife_waves;
\ No newline at end of file
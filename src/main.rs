@@ -6,8 +6,29 @@ mod knife;
 mod points;
 mod ui;
 mod coin;
+mod collision;
+mod controls;
+mod health;
+mod stats;
+mod storage;
+mod highscores;
+mod options;
+mod headless;
+mod random;
+mod replay;
+mod settings;
+mod difficulty;
+mod powerups;
 
+use std::time::Duration;
+
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::ecs::schedule::{LogLevel, ScheduleBuildSettings, ScheduleLabel};
 use bevy::prelude::*;
+use bevy::render::settings::WgpuSettings;
+use bevy::render::RenderPlugin;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
 use bevy_asset_loader::asset_collection::AssetCollection;
 use bevy_asset_loader::loading_state::config::ConfigureLoadingState;
 use bevy_asset_loader::loading_state::{LoadingState, LoadingStateAppExt};
@@ -16,15 +37,36 @@ use bevy_kira_audio::AudioPlugin;
 use bevy_kira_audio::AudioSource as KiraAudioSource;
 use coin::coin::{CoinAssets, CoinChannel, CoinPlugin};
 use coin::coin_spawner::CoinSpawnerPlugin;
+use difficulty::difficulty::{apply_difficulty, ActiveDifficulty, DifficultyAssets, DifficultyPlugin, DifficultyProfile};
+use collision::collision::CollisionPlugin;
+use controls::controls::{ActionMap, ControlsPlugin};
 use gravity::gravity::GravityPlugin;
+use headless::headless::HeadlessPlugin;
+use health::health::HealthPlugin;
+use highscores::highscores::HighScoresPlugin;
 use knife::knife::{KnifeAudios, KnifeChannel, KnifePlugin};
+use knife::knife_near_miss::{NearMissChannel, NearMissPlugin};
 use knife::knife_spawner::KnifeSpawnerPlugin;
+use knife::knife_waves::{KnifeWavesPlugin, WaveAssets};
 use movement::movement::MovementPlugin;
+use options::options::LaunchOptions;
+use player::player_dash::DashPlugin;
 use player::player_input::InputPlugin;
+use player::player_jump::JumpPlugin;
 use player::player::{PlayerAnimationAssets, PlayerChannel, PlayerAudioSources, PlayerPlugin};
 use points::points::{Points, PointsPlugin};
-use terrain::terrain::TerrainPlugin;
+use powerups::powerups::PowerUpsPlugin;
+use random::random::{GameRng, RandomPlugin};
+use replay::replay::ReplayPlugin;
+use settings::settings::{GameSettings, SettingsPlugin};
+use stats::stats::RunStatsPlugin;
+use terrain::terrain::{ArenaAssets, TerrainPlugin};
+use ui::controls_menu::ControlsMenuPlugin;
+use ui::game_over::GameOverPlugin;
 use ui::main_menu::MainMenuPlugin;
+use ui::pause_menu::PauseMenuPlugin;
+use ui::settings_menu::SettingsMenuPlugin;
+use ui::text_input::TextInputPlugin;
 use ui::ui::UIPlugin;
 
 // Window
@@ -36,7 +78,19 @@ enum GameState {
     #[default]
     AssetLoading,
     Menu,
-    Game
+    Controls,
+    Settings,
+    Game,
+    GameOver,
+    Restarting
+}
+
+#[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, SubStates)]
+#[source(GameState = GameState::Game)]
+enum PauseState {
+    #[default]
+    Running,
+    Paused
 }
 
 #[derive(Component)]
@@ -45,46 +99,108 @@ pub struct CleanupGameStateExit;
 #[derive(Component)]
 pub struct CleanupMenuStateExit;
 
+#[derive(Component)]
+pub struct CleanupGameOverStateExit;
+
+#[derive(Component)]
+pub struct CleanupControlsStateExit;
+
+#[derive(Component)]
+pub struct CleanupSettingsStateExit;
+
+#[derive(Resource)]
+pub struct PlayField {
+    pub width: f32,
+    pub height: f32
+}
+
+impl Default for PlayField {
+    fn default() -> Self {
+        Self { width: WW, height: WH }
+    }
+}
+
 #[derive(Resource)]
 pub struct Level {
     pub value: i32
 }
 
-const LEVEL_UP_TIMER: f32 = 5.0;
 #[derive(Resource)]
 pub struct LevelIncreaseTimer(Timer);
 
 impl Default for LevelIncreaseTimer {
     fn default() -> Self {
-        Self(Timer::from_seconds(LEVEL_UP_TIMER, TimerMode::Repeating))
+        Self(Timer::from_seconds(DifficultyProfile::default().level_duration.at(0), TimerMode::Repeating))
     }
 }
 
 #[derive(Event)]
 pub struct LevelUpEvent;
 
+// Order of a frame of the game in `Update`, a seed only plays out the same way when every frame does the same things in the same order
+#[derive(SystemSet, Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum GameplaySet {
+    // The level goes up
+    Level,
+    // Spawn timers and waves drop knives, coins and power-ups
+    Spawn,
+    // The player moves, jumps and dashes and takes the hits and pickups of the physics ticks
+    Player,
+    // Power-ups start, run out and change the knives and points
+    PowerUps,
+    // Knives and coins react to what the physics ticks did to them
+    World,
+    // Points, combos and run stats add up what happened
+    Score,
+    // Only shows the game
+    Ui
+}
+
 fn main() {
-    App::new()
-    .add_plugins(
-        DefaultPlugins
-                    .set(ImagePlugin::default_nearest())
-                    .set(WindowPlugin {
-                        primary_window: Some(Window {
-                            focused: true,
-                            resolution: (WW, WH).into(),
-                            title: "Avoid the Knifes".to_string(),
+    let options = LaunchOptions::from_args();
+    let mut app = App::new();
+
+    if options.headless {
+        app.add_plugins(
+            DefaultPlugins
+                        .set(ImagePlugin::default_nearest())
+                        .set(WindowPlugin {
+                            primary_window: None,
+                            exit_condition: ExitCondition::DontExit,
+                            close_when_requested: false
+                        })
+                        .set(RenderPlugin {
+                            render_creation: WgpuSettings { backends: None, ..default() }.into(),
+                            ..default()
+                        })
+                        .disable::<WinitPlugin>(),
+                )
+        .add_plugins((ScheduleRunnerPlugin::run_loop(Duration::ZERO), HeadlessPlugin));
+    } else {
+        app.add_plugins(
+            DefaultPlugins
+                        .set(ImagePlugin::default_nearest())
+                        .set(WindowPlugin {
+                            primary_window: Some(Window {
+                                focused: true,
+                                resolution: (WW, WH).into(),
+                                title: "Avoid the Knifes".to_string(),
+                                ..default()
+                            }),
                             ..default()
                         }),
-                        ..default()
-                    }),
-            )
+                );
+    }
+
+    app
     .add_plugins(AudioPlugin)
-    .add_plugins((InputPlugin { state: GameState::Game }, MovementPlugin { state: GameState::Game }, TerrainPlugin { state: GameState::Game }, GravityPlugin { state: GameState::Game }))
-    .add_plugins(PointsPlugin)
-    .add_plugins((UIPlugin { state: GameState::Game }, MainMenuPlugin { state: GameState::Menu }))
-    .add_plugins(PlayerPlugin { state: GameState::Game })
-    .add_plugins((CoinPlugin { state: GameState::Game }, CoinSpawnerPlugin { state: GameState::Game }))
-    .add_plugins((KnifePlugin { state: GameState::Game }, KnifeSpawnerPlugin { state: GameState::Game }))
+    .add_plugins((InputPlugin { state: GameState::Game }, MovementPlugin { state: GameState::Game }, TerrainPlugin { state: GameState::Game }, GravityPlugin { state: GameState::Game }, CollisionPlugin { state: GameState::Game }))
+    .add_plugins((RandomPlugin, ReplayPlugin, ControlsPlugin, SettingsPlugin, DifficultyPlugin))
+    .add_plugins((PointsPlugin { state: GameState::Game }, RunStatsPlugin { state: GameState::Game }, HighScoresPlugin { state: GameState::GameOver }))
+    .add_plugins((UIPlugin { state: GameState::Game }, MainMenuPlugin { state: GameState::Menu }, GameOverPlugin { state: GameState::GameOver }, PauseMenuPlugin { state: PauseState::Paused }, ControlsMenuPlugin { state: GameState::Controls }, SettingsMenuPlugin { state: GameState::Settings }, TextInputPlugin))
+    .add_plugins((PlayerPlugin { state: GameState::Game }, JumpPlugin { state: GameState::Game }, DashPlugin { state: GameState::Game }, HealthPlugin { state: GameState::Game }))
+    .add_plugins((CoinPlugin { state: GameState::Game }, CoinSpawnerPlugin { state: GameState::Game }, PowerUpsPlugin { state: GameState::Game }))
+    .add_plugins((KnifePlugin { state: GameState::Game }, KnifeSpawnerPlugin { state: GameState::Game }, KnifeWavesPlugin { state: GameState::Game }, NearMissPlugin { state: GameState::Game }))
     .add_audio_channel::<BackgroundChannel>()
     .add_loading_state(
         LoadingState::new(GameState::AssetLoading).continue_to_state(GameState::Menu)
@@ -92,17 +208,56 @@ fn main() {
         .load_collection::<CoinAssets>()
         .load_collection::<PlayerAnimationAssets>().load_collection::<PlayerAudioSources>()
         .load_collection::<KnifeAudios>()
+        .load_collection::<DifficultyAssets>()
+        .load_collection::<ArenaAssets>()
+        .load_collection::<WaveAssets>()
     )
     .add_systems(OnExit(GameState::Menu), cleanup_system::<CleanupMenuStateExit>)
-    .add_systems(OnExit(GameState::Game), (cleanup_system::<CleanupGameStateExit>, reset, stop_channels))
-    .add_systems(Update, (level_timer_update, play_background_music).run_if(in_state(GameState::Game)))
+    .add_systems(OnEnter(GameState::Game), reset.after(apply_difficulty))
+    .add_systems(OnExit(GameState::Game), (cleanup_system::<CleanupGameStateExit>, stop_channels))
+    .add_systems(OnExit(GameState::GameOver), cleanup_system::<CleanupGameOverStateExit>)
+    .add_systems(OnExit(GameState::Controls), cleanup_system::<CleanupControlsStateExit>)
+    .add_systems(OnExit(GameState::Settings), cleanup_system::<CleanupSettingsStateExit>)
+    .add_systems(OnEnter(GameState::Restarting), restart)
+    .add_systems(OnEnter(PauseState::Paused), (pause_channels, pause_time))
+    .add_systems(OnExit(PauseState::Paused), (resume_channels, resume_time))
+    .configure_sets(Update, (GameplaySet::Level, GameplaySet::Spawn, GameplaySet::Player, GameplaySet::PowerUps, GameplaySet::World, GameplaySet::Score, GameplaySet::Ui).chain())
+    .add_systems(Update, (level_timer_update.in_set(GameplaySet::Level), play_background_music).run_if(in_state(GameState::Game)).run_if(in_state(PauseState::Running)))
     .add_event::<LevelUpEvent>()
     .insert_resource(Msaa::Off)
     .insert_resource(Level { value: 0 })
+    .insert_resource(options)
+    .init_resource::<PlayField>()
     .init_resource::<LevelIncreaseTimer>()
     .init_state::<GameState>()
-    .add_systems(Startup, setup_camera)
-    .run();
+    .add_sub_state::<PauseState>()
+    .add_systems(Startup, setup_camera);
+
+    configure_ambiguity_detection(&mut app);
+    app.run();
+}
+
+// Systems using the same data without an order between them are reported when the schedules are built, a seed only replays the same run once they are ordered.
+// What is only drawn, and what only menus change outside of a run, is left out of the report
+fn configure_ambiguity_detection(app: &mut App) {
+    for schedule in [Update.intern(), FixedUpdate.intern()] {
+        app.edit_schedule(schedule, |schedule| {
+            schedule.set_build_settings(ScheduleBuildSettings { ambiguity_detection: LogLevel::Warn, ..default() });
+        });
+    }
+
+    app.allow_ambiguous_component::<Text>()
+        .allow_ambiguous_component::<Sprite>()
+        .allow_ambiguous_component::<TextureAtlas>()
+        .allow_ambiguous_component::<Handle<Image>>()
+        .allow_ambiguous_component::<Visibility>()
+        .allow_ambiguous_component::<BackgroundColor>()
+        .allow_ambiguous_component::<Style>()
+        .allow_ambiguous_resource::<NextState<GameState>>()
+        .allow_ambiguous_resource::<GameSettings>()
+        .allow_ambiguous_resource::<ActionMap>()
+        // Every consumer draws from its own stream, the order they draw in does not change the numbers
+        .allow_ambiguous_resource::<GameRng>();
 }
 
 fn setup_camera(mut commands: Commands) {
@@ -115,19 +270,25 @@ fn cleanup_system<T: Component>(mut commands: Commands, q: Query<Entity, With<T>
     }
 }
 
-fn level_timer_update(time: Res<Time>, mut leveled_up: EventWriter<LevelUpEvent>, mut level_up_timer: ResMut<LevelIncreaseTimer>, mut level: ResMut<Level>) {
+fn level_timer_update(time: Res<Time>, mut leveled_up: EventWriter<LevelUpEvent>, mut level_up_timer: ResMut<LevelIncreaseTimer>, mut level: ResMut<Level>,
+    difficulty: Res<ActiveDifficulty>) {
     level_up_timer.0.tick(time.delta());
     
     if level_up_timer.0.just_finished() {
         leveled_up.send(LevelUpEvent);
         level.value += 1;
+        level_up_timer.0.set_duration(Duration::from_secs_f32(difficulty.profile.level_duration.at(level.value)));
     }
 }
 
-fn reset(mut level: ResMut<Level>, mut points: ResMut<Points>, mut level_timer: ResMut<LevelIncreaseTimer>) {
+fn reset(mut level: ResMut<Level>, mut points: ResMut<Points>, mut level_timer: ResMut<LevelIncreaseTimer>, difficulty: Res<ActiveDifficulty>) {
     level.value = 0;
     points.value = 0;
-    level_timer.0 = Timer::from_seconds(LEVEL_UP_TIMER, TimerMode::Repeating);
+    level_timer.0 = Timer::from_seconds(difficulty.profile.level_duration.at(0), TimerMode::Repeating);
+}
+
+fn restart(mut game_state: ResMut<NextState<GameState>>) {
+    game_state.set(GameState::Game);
 }
 
 #[derive(Resource)]
@@ -151,10 +312,48 @@ fn stop_channels(
     background_channel: Res<AudioChannel<BackgroundChannel>>,
     player_channel: Res<AudioChannel<PlayerChannel>>,
     knife_channel: Res<AudioChannel<KnifeChannel>>,
-    coin_channel: Res<AudioChannel<CoinChannel>>) 
+    coin_channel: Res<AudioChannel<CoinChannel>>,
+    near_miss_channel: Res<AudioChannel<NearMissChannel>>) 
 {
     background_channel.stop();
     player_channel.stop();
     knife_channel.stop();
     coin_channel.stop();
+    near_miss_channel.stop();
+}
+
+fn pause_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn resume_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn pause_channels(
+    background_channel: Res<AudioChannel<BackgroundChannel>>,
+    player_channel: Res<AudioChannel<PlayerChannel>>,
+    knife_channel: Res<AudioChannel<KnifeChannel>>,
+    coin_channel: Res<AudioChannel<CoinChannel>>,
+    near_miss_channel: Res<AudioChannel<NearMissChannel>>)
+{
+    background_channel.pause();
+    player_channel.pause();
+    knife_channel.pause();
+    coin_channel.pause();
+    near_miss_channel.pause();
+}
+
+fn resume_channels(
+    background_channel: Res<AudioChannel<BackgroundChannel>>,
+    player_channel: Res<AudioChannel<PlayerChannel>>,
+    knife_channel: Res<AudioChannel<KnifeChannel>>,
+    coin_channel: Res<AudioChannel<CoinChannel>>,
+    near_miss_channel: Res<AudioChannel<NearMissChannel>>)
+{
+    background_channel.resume();
+    player_channel.resume();
+    knife_channel.resume();
+    coin_channel.resume();
+    near_miss_channel.resume();
 }
\ No newline at end of file
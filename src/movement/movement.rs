@@ -1,6 +1,7 @@
 use::bevy::prelude::*;
+use bevy::transform::TransformSystem;
 
-use crate::{gravity::gravity::Gravity, GameState};
+use crate::{gravity::gravity::Gravity, options::options::LaunchOptions, GameState, GameplaySet, PauseState};
 
 pub struct MovementPlugin<GameState: States> {
     pub state: GameState
@@ -8,7 +9,39 @@ pub struct MovementPlugin<GameState: States> {
 
 impl Plugin for MovementPlugin<GameState> {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (move_entity, check_direction).run_if(in_state(self.state.clone())));
+        app.add_systems(Startup, set_tick_rate);
+        app.configure_sets(FixedUpdate, (PhysicsSet::Forces, PhysicsSet::Integrate, PhysicsSet::Detect, PhysicsSet::Resolve, PhysicsSet::Collide).chain());
+        app.add_systems(FixedFirst, (restore_physics_translation, insert_physics_interpolation).run_if(in_state(self.state.clone())));
+        app.add_systems(FixedUpdate, move_entity.in_set(PhysicsSet::Integrate).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(FixedLast, store_physics_translation.run_if(in_state(self.state.clone())));
+        app.add_systems(Update, check_direction.in_set(GameplaySet::Player).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(PostUpdate, interpolate_translation.before(TransformSystem::TransformPropagate).run_if(in_state(self.state.clone())));
+    }
+}
+
+// Physics runs on a fixed timestep: forces change velocities, velocities move entities, then collisions are detected and resolved
+#[derive(SystemSet, Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum PhysicsSet {
+    Forces,
+    Integrate,
+    Detect,
+    // Bodies are put back on the terrain and inside the play field, so the reactions after it see where things really are
+    Resolve,
+    Collide
+}
+
+// Translation of the last two physics ticks, rendering blends between them
+#[derive(Component)]
+pub struct PhysicsInterpolation {
+    previous: Vec3,
+    current: Vec3
+}
+
+impl PhysicsInterpolation {
+    // Moving without blending, rendering would otherwise sweep across the screen from the old position
+    pub fn teleport(&mut self, translation: Vec3) {
+        self.previous = translation;
+        self.current = translation;
     }
 }
 
@@ -19,29 +52,40 @@ pub struct Movement {
 }
 
 #[derive(Component)]
+// `gravity_scale` multiplies the pull of gravity, so the same mass can fall faster or slower
+// `time_scale` is how fast time passes for the body, below 1 it moves and falls in slow motion
+// `restitution` is the share of the landing speed the body bounces back up with, 0 stops it dead
+// `terminal_velocity` is the fastest the body can fall
 pub struct Body {
     pub mass: f32,
     pub velocity: Vec2,
-    pub direction: i8
+    pub direction: i8,
+    pub gravity_scale: f32,
+    pub time_scale: f32,
+    pub restitution: f32,
+    pub terminal_velocity: f32
 }
 
+const DEFAULT_TERMINAL_VELOCITY: f32 = 1500.0;
+
 impl Default for Body {
     fn default() -> Self {
-        Self { mass: 100.0, velocity: Vec2::ZERO, direction: 1 }
+        Self { mass: 100.0, velocity: Vec2::ZERO, direction: 1, gravity_scale: 1.0, time_scale: 1.0, restitution: 0.0, terminal_velocity: DEFAULT_TERMINAL_VELOCITY }
     }
 }
 
+fn set_tick_rate(mut commands: Commands, options: Res<LaunchOptions>) {
+    commands.insert_resource(Time::<Fixed>::from_hz(options.tick_rate));
+}
+
 fn move_entity(mut movement_query: Query<(&Movement, &mut Transform)>, time: Res<Time>) {
     for (movement, mut transform) in movement_query.iter_mut() {
-        transform.translation.x += movement.body.velocity.x * time.delta_seconds();
-
-        if movement.gravity.is_touching_terrain == false {
-            transform.translation.y += movement.body.velocity.y * time.delta_seconds();
-        }
+        let delta = time.delta_seconds() * movement.body.time_scale;
+        transform.translation += (movement.body.velocity * delta).extend(0.0);
     }
 }
 
-fn check_direction(mut movement_query: Query<(&mut Movement, &mut Sprite)>) {
+pub fn check_direction(mut movement_query: Query<(&mut Movement, &mut Sprite)>) {
     for (mut movement, mut sprite) in &mut movement_query {
         
         if movement.body.velocity != Vec2::ZERO {
@@ -66,4 +110,37 @@ fn check_direction(mut movement_query: Query<(&mut Movement, &mut Sprite)>) {
         }
         
     }
-}
\ No newline at end of file
+}
+
+// Bodies spawned since the last tick
+type UninterpolatedBodies = (With<Movement>, Without<PhysicsInterpolation>);
+
+fn insert_physics_interpolation(mut commands: Commands, query: Query<(Entity, &Transform), UninterpolatedBodies>) {
+    for (entity, transform) in query.iter() {
+        commands.entity(entity).insert(PhysicsInterpolation {
+            previous: transform.translation,
+            current: transform.translation
+        });
+    }
+}
+
+fn restore_physics_translation(mut query: Query<(&mut PhysicsInterpolation, &mut Transform)>) {
+    for (mut interpolation, mut transform) in &mut query {
+        transform.translation = interpolation.current;
+        interpolation.previous = interpolation.current;
+    }
+}
+
+fn store_physics_translation(mut query: Query<(&mut PhysicsInterpolation, &Transform)>) {
+    for (mut interpolation, transform) in &mut query {
+        interpolation.current = transform.translation;
+    }
+}
+
+fn interpolate_translation(fixed_time: Res<Time<Fixed>>, mut query: Query<(&PhysicsInterpolation, &mut Transform)>) {
+    let alpha = fixed_time.overstep_fraction();
+
+    for (interpolation, mut transform) in &mut query {
+        transform.translation = interpolation.previous.lerp(interpolation.current, alpha);
+    }
+}
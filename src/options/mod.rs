@@ -0,0 +1 @@
+pub mod options;
\ No newline at end of file
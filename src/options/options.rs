@@ -0,0 +1,55 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use::bevy::prelude::*;
+
+const DEFAULT_TICK_RATE: f64 = 60.0;
+
+#[derive(Resource, Clone)]
+pub struct LaunchOptions {
+    pub headless: bool,
+    pub runs: u32,
+    pub seed: Option<u64>,
+    pub replay: Option<PathBuf>,
+    pub tick_rate: f64
+}
+
+impl Default for LaunchOptions {
+    fn default() -> Self {
+        Self { headless: false, runs: 1, seed: None, replay: None, tick_rate: DEFAULT_TICK_RATE }
+    }
+}
+
+impl LaunchOptions {
+    pub fn from_args() -> Self {
+        let mut options = Self::default();
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--headless" => options.headless = true,
+                "--runs" => options.runs = parse_value(&arg, args.next()).unwrap_or(options.runs),
+                "--seed" => options.seed = parse_value(&arg, args.next()),
+                "--replay" => options.replay = parse_value(&arg, args.next()),
+                "--tick-rate" => options.tick_rate = parse_value(&arg, args.next()).filter(|rate: &f64| *rate > 0.0).unwrap_or(options.tick_rate),
+                _ => eprintln!("Ignoring unknown argument {arg}")
+            }
+        }
+
+        options
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: Option<String>) -> Option<T> {
+    let parsed = value.as_deref().and_then(|value| value.parse().ok());
+
+    if parsed.is_none() {
+        eprintln!("Missing or invalid value for {flag}");
+    }
+
+    parsed
+}
+
+pub fn is_headless(options: Res<LaunchOptions>) -> bool {
+    options.headless
+}
@@ -1,2 +1,4 @@
 pub mod player;
-pub mod player_input;
\ No newline at end of file
+pub mod player_dash;
+pub mod player_input;
+pub mod player_jump;
\ No newline at end of file
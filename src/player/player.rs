@@ -1,19 +1,32 @@
 use bevy::prelude::*;
-use bevy::window::PrimaryWindow;
 use bevy_asset_loader::asset_collection::AssetCollection;
 use bevy_kira_audio::prelude::*;
 use bevy_kira_audio::AudioSource as KiraAudioSource;
 
-use crate::gravity::gravity::Gravity;
+use crate::collision::collision::{Collider, CollisionLayer, CollisionLayers};
+use crate::gravity::gravity::{entity_is_touching_terrain, Gravity};
+use crate::health::health::{Health, Invulnerable, Lives};
 use crate::knife::knife::PlayerHitEvent;
-use crate::movement::movement::{Body, Movement};
+use crate::movement::movement::{check_direction, Body, Movement, PhysicsInterpolation, PhysicsSet};
 use crate::coin::coin::CoinTouchedEvent;
-use crate::points::points::Points;
-use crate::{CleanupGameStateExit, GameState};
+use crate::points::points::ScoreEvent;
+use crate::powerups::powerups::{ActivePowerUps, PowerUp};
+use crate::terrain::terrain::{apply_arena, ActiveArena};
+use crate::{CleanupGameStateExit, GameState, GameplaySet, PauseState, PlayField};
 
 use super::player_input::{InputDirection, MovementInputEvent};
+use super::player_dash::{update_dash, Dash, Dashing};
+use super::player_jump::Jump;
 
 const PLAYER_SPEED: f32 = 500.0;
+const PLAYER_MAX_HEALTH: f32 = 3.0;
+const PLAYER_LIVES: i32 = 2;
+// Extra jumps in the air, set to 0 to turn the double jump off
+const PLAYER_AIR_JUMPS: u32 = 1;
+// Frame of the walking animation shown while in the air
+const JUMP_FRAME: usize = 1;
+// Damage for falling through a gap in the terrain
+const FALL_DAMAGE: f32 = 1.0;
 
 pub struct PlayerPlugin<GameState: States> {
     pub state: GameState,
@@ -22,11 +35,13 @@ pub struct PlayerPlugin<GameState: States> {
 impl Plugin for PlayerPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.add_audio_channel::<PlayerChannel>();
-        app.add_systems(OnEnter(GameState::Game), spawn_player
+        app.add_systems(OnEnter(GameState::Game), spawn_player.after(apply_arena)
             .run_if(in_state(self.state.clone())));
-        app.add_systems(Update, (animate_sprite, basic_state_machine, listen_movement_input, keep_player_in_bounds, listen_for_knives, listen_for_coins)
-            .run_if(in_state(self.state.clone())));
-        app.add_systems(Update, play_footsteps.run_if(in_state(self.state.clone())));
+        // Input sets the speed before the facing is read from it and the jump and dash build on both
+        app.add_systems(Update, (listen_movement_input.before(check_direction), (basic_state_machine, animate_sprite).chain().after(update_dash), listen_for_knives, listen_for_coins).in_set(GameplaySet::Player)
+            .run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(FixedUpdate, (keep_player_in_bounds, respawn_after_fall).chain().after(entity_is_touching_terrain).in_set(PhysicsSet::Resolve).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(Update, play_footsteps.in_set(GameplaySet::Ui).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
@@ -57,6 +72,11 @@ pub struct PlayerChannel;
 struct PlayerBundle {
     player: Player,
     movement: Movement,
+    jump: Jump,
+    dash: Dash,
+    collider: Collider,
+    health: Health,
+    lives: Lives,
     sprite: SpriteBundle,
     atlas: TextureAtlas,
     animation_timer: AnimationTimer
@@ -74,10 +94,11 @@ pub struct Player {
 #[derive(Clone, Copy, Eq, PartialEq, Debug, Hash, States)]
 pub enum PlayerState {
     Idle,
-    Walking 
+    Walking,
+    Jumping
 }
 
-fn spawn_player(mut commands: Commands, animations: Res<PlayerAnimationAssets>) {
+fn spawn_player(mut commands: Commands, animations: Res<PlayerAnimationAssets>, arena: Res<ActiveArena>) {
     
     commands.spawn(PlayerBundle {
         player: Player { speed: PLAYER_SPEED, state: PlayerState::Idle },
@@ -89,6 +110,7 @@ fn spawn_player(mut commands: Commands, animations: Res<PlayerAnimationAssets>)
             },
             transform: Transform {
                 scale: Vec3::splat(4.0),
+                translation: arena.player_spawn,
                 ..default()
             },
             ..default()
@@ -103,39 +125,94 @@ fn spawn_player(mut commands: Commands, animations: Res<PlayerAnimationAssets>)
                 ..default()
             }
         },
+        jump: Jump::new(PLAYER_AIR_JUMPS),
+        dash: Dash::default(),
+        collider: Collider::new(Vec2::new(22.0, 30.0), CollisionLayers::new(CollisionLayer::PLAYER, CollisionLayer::KNIFE | CollisionLayer::COIN | CollisionLayer::TERRAIN)),
+        health: Health::new(PLAYER_MAX_HEALTH),
+        lives: Lives { value: PLAYER_LIVES },
         atlas: TextureAtlas::from(animations.layout.clone()),
         animation_timer: AnimationTimer(Timer::from_seconds(0.125, TimerMode::Repeating))
     })
     .insert((Name::new("Player"), CleanupGameStateExit));
 }
 
-fn listen_movement_input(mut ev_movement: EventReader<MovementInputEvent>, mut movement_query: Query<(&mut Movement, &Player)>) {
+fn listen_movement_input(mut ev_movement: EventReader<MovementInputEvent>, mut movement_query: Query<(&mut Movement, &Player, Has<Dashing>)>) {
     
-    if let Ok((mut movement, player)) = movement_query.get_single_mut() {
+    if let Ok((mut movement, player, dashing)) = movement_query.get_single_mut() {
         for input_direction in ev_movement.read() {
+            // The dash controls the horizontal speed until it ends
+            if dashing {
+                continue;
+            }
+
             match input_direction.direction {
-                InputDirection::Left => movement.body.velocity.x = -player.speed,
-                InputDirection::Right => movement.body.velocity.x = player.speed,
+                InputDirection::Left => movement.body.velocity.x = -player.speed * input_direction.strength,
+                InputDirection::Right => movement.body.velocity.x = player.speed * input_direction.strength,
                 InputDirection::None => movement.body.velocity.x = 0.0,
             }
         }
     }
 }
 
-fn listen_for_knives(mut ev_player_hit: EventReader<PlayerHitEvent>, mut game_state: ResMut<NextState<GameState>>) {
-    for event in ev_player_hit.read() {
-        game_state.set(GameState::Menu);
+// The health of the player and whether a hit can land right now
+type HitPlayer = (Entity, &'static mut Health, &'static mut Lives, Has<Invulnerable>, Has<Dashing>);
+
+fn listen_for_knives(mut commands: Commands, mut ev_player_hit: EventReader<PlayerHitEvent>, mut game_state: ResMut<NextState<GameState>>, mut power_ups: ResMut<ActivePowerUps>,
+    mut player_query: Query<HitPlayer, With<Player>>) {
+
+    if let Ok((entity, mut health, mut lives, mut invulnerable, dashing)) = player_query.get_single_mut() {
+        for event in ev_player_hit.read() {
+            if invulnerable || dashing {
+                continue;
+            }
+
+            // The shield breaks instead, the invulnerability keeps the knives right behind from hitting
+            if power_ups.consume(PowerUp::Shield) {
+                commands.entity(entity).insert(Invulnerable::default());
+                invulnerable = true;
+                continue;
+            }
+
+            if !lose_health(&mut health, &mut lives, event.damage) {
+                game_state.set(GameState::GameOver);
+                return;
+            }
+
+            commands.entity(entity).insert(Invulnerable::default());
+            invulnerable = true;
+        }
+    }
+}
+
+// Spends a life and refills the health once it runs out, false when there was no life left to spend
+fn lose_health(health: &mut Health, lives: &mut Lives, damage: f32) -> bool {
+    health.take_damage(damage);
+
+    if health.is_dead() {
+        if lives.value == 0 {
+            return false;
+        }
+
+        lives.value -= 1;
+        health.refill();
     }
+
+    true
 }
 
-fn listen_for_coins(mut ev_coin_collected: EventReader<CoinTouchedEvent>, mut points: ResMut<Points>) {
+fn listen_for_coins(mut ev_coin_collected: EventReader<CoinTouchedEvent>, mut ev_score: EventWriter<ScoreEvent>) {
     for event in ev_coin_collected.read() {
-        points.value += event.value;
+        ev_score.send(ScoreEvent::combo_at(event.value, event.position));
     }
 }
 
-fn animate_sprite(time: Res<Time>, mut query: Query<(&mut AnimationTimer, &mut TextureAtlas), With<Player>>) {
-    for (mut timer, mut sprite) in &mut query  {
+fn animate_sprite(time: Res<Time>, mut query: Query<(&Player, &mut AnimationTimer, &mut TextureAtlas)>) {
+    for (player, mut timer, mut sprite) in &mut query  {
+        // The jump pose is a single frame
+        if player.state == PlayerState::Jumping {
+            continue;
+        }
+
         timer.0.tick(time.delta());
         if timer.0.just_finished() {
             sprite.index = (sprite.index + 1) % 4;
@@ -157,7 +234,18 @@ fn play_footsteps(audios: Res<PlayerAudioSources>, player_channel: Res<AudioChan
 
 fn basic_state_machine(mut query: Query<(&mut Player, &Movement, &mut AnimationTimer, &mut Handle<Image>, &mut TextureAtlas)>, animations: Res<PlayerAnimationAssets>) {
     for (mut player, movement, mut timer, mut sprite, mut atlas) in &mut query {
-        
+        let grounded = Jump::is_grounded(movement);
+
+        if !grounded && player.state != PlayerState::Jumping {
+            player.state = PlayerState::Jumping;
+            *sprite = animations.walking.clone();
+            *atlas = TextureAtlas { layout: animations.layout.clone(), index: JUMP_FRAME };
+        }
+
+        if !grounded {
+            continue;
+        }
+
         if movement.body.velocity.x == 0.0 && player.state != PlayerState::Idle {
             player.state = PlayerState::Idle;
             *sprite = animations.idle.clone();
@@ -174,11 +262,9 @@ fn basic_state_machine(mut query: Query<(&mut Player, &Movement, &mut AnimationT
     }
 }
 
-fn keep_player_in_bounds(window_query: Query<&Window, With<PrimaryWindow>>, mut query: Query<(&mut Transform, &Sprite), With<Player>>) {
-    let window = window_query.get_single().unwrap();
-    
-    let half_width = window.width() / 2.0;
-    let half_height = window.height() / 2.0;
+fn keep_player_in_bounds(play_field: Res<PlayField>, mut query: Query<(&mut Transform, &Sprite), With<Player>>) {
+    let half_width = play_field.width / 2.0;
+    let half_height = play_field.height / 2.0;
 
     for (mut transform, sprite) in query.iter_mut() {
         let sprite_size = sprite.custom_size.unwrap_or(Vec2::ZERO) * transform.scale.truncate();
@@ -186,7 +272,38 @@ fn keep_player_in_bounds(window_query: Query<&Window, With<PrimaryWindow>>, mut
         transform.translation.x = transform.translation.x
             .clamp(-half_width + sprite_size.x / 2.0, half_width - sprite_size.x / 2.0);
 
-        transform.translation.y = transform.translation.y
-            .clamp(-half_height + sprite_size.y / 2.0, half_height - sprite_size.y / 2.0);
+        // The bottom stays open, so the player can fall through gaps in the terrain
+        transform.translation.y = transform.translation.y.min(half_height - sprite_size.y / 2.0);
+    }
+}
+
+// Everything a fall changes on the player, the sprite tells when it is out of sight
+type FallingPlayer = (Entity, &'static mut Transform, &'static mut Movement, Option<&'static mut PhysicsInterpolation>, &'static mut Health, &'static mut Lives, &'static Sprite);
+
+// Falling out of the play field costs health and puts the player back at the start of the arena
+// The damage is taken directly, invulnerability and dashing only keep knives away and would let the fall go free
+fn respawn_after_fall(mut commands: Commands, play_field: Res<PlayField>, arena: Res<ActiveArena>, mut game_state: ResMut<NextState<GameState>>,
+    mut query: Query<FallingPlayer, With<Player>>) {
+
+    for (entity, mut transform, mut movement, interpolation, mut health, mut lives, sprite) in query.iter_mut() {
+        let sprite_height = sprite.custom_size.unwrap_or(Vec2::ZERO).y * transform.scale.y;
+
+        if transform.translation.y >= -play_field.height / 2.0 - sprite_height {
+            continue;
+        }
+
+        transform.translation = arena.player_spawn;
+        movement.body.velocity = Vec2::ZERO;
+
+        if let Some(mut interpolation) = interpolation {
+            interpolation.teleport(arena.player_spawn);
+        }
+
+        if !lose_health(&mut health, &mut lives, FALL_DAMAGE) {
+            game_state.set(GameState::GameOver);
+            return;
+        }
+
+        commands.entity(entity).insert(Invulnerable::default());
     }
 }
\ No newline at end of file
@@ -0,0 +1,139 @@
+use::bevy::prelude::*;
+
+use crate::movement::movement::Movement;
+use crate::{CleanupGameStateExit, GameState, GameplaySet, PauseState};
+
+use super::player::Player;
+use super::player_input::MovementInputEvent;
+use super::player_jump::jump;
+
+pub struct DashPlugin<GameState: States> {
+    pub state: GameState
+}
+
+const DASH_SPEED: f32 = 1400.0;
+const DASH_TIME: f32 = 0.15;
+const DASH_COOLDOWN: f32 = 1.2;
+const AFTERIMAGE_INTERVAL: f32 = 0.03;
+const AFTERIMAGE_LIFETIME: f32 = 0.25;
+const AFTERIMAGE_ALPHA: f32 = 0.5;
+
+impl Plugin for DashPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, (start_dash, update_dash, spawn_afterimages, fade_afterimages).chain().in_set(GameplaySet::Player).after(jump)
+            .run_if(in_state(self.state)).run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Component)]
+pub struct Dash {
+    pub cooldown: Timer,
+    was_held: bool
+}
+
+impl Default for Dash {
+    fn default() -> Self {
+        let mut cooldown = Timer::from_seconds(DASH_COOLDOWN, TimerMode::Once);
+        // The first dash of a run is ready right away
+        cooldown.tick(cooldown.duration());
+
+        Self { cooldown, was_held: false }
+    }
+}
+
+impl Dash {
+    // 0 right after dashing, 1 when the dash can be used again
+    pub fn readiness(&self) -> f32 {
+        self.cooldown.fraction()
+    }
+}
+
+// Present while the dash is moving the player, hits are ignored for as long as it lasts
+#[derive(Component)]
+pub struct Dashing {
+    timer: Timer,
+    afterimage_timer: Timer,
+    direction: f32
+}
+
+#[derive(Component)]
+struct Afterimage {
+    timer: Timer
+}
+
+fn start_dash(mut commands: Commands, time: Res<Time>, mut ev_movement: EventReader<MovementInputEvent>,
+    mut query: Query<(Entity, &mut Dash, &Movement, Has<Dashing>), With<Player>>) {
+
+    let Ok((entity, mut dash, movement, dashing)) = query.get_single_mut() else {
+        return;
+    };
+
+    dash.cooldown.tick(time.delta());
+
+    let held = ev_movement.read().last().is_some_and(|event| event.dash);
+    let just_pressed = held && !dash.was_held;
+    dash.was_held = held;
+
+    if just_pressed && !dashing && dash.cooldown.finished() {
+        dash.cooldown.reset();
+        commands.entity(entity).insert(Dashing {
+            timer: Timer::from_seconds(DASH_TIME, TimerMode::Once),
+            afterimage_timer: Timer::from_seconds(AFTERIMAGE_INTERVAL, TimerMode::Repeating),
+            direction: movement.body.direction as f32
+        });
+    }
+}
+
+pub fn update_dash(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Dashing, &mut Movement)>) {
+    for (entity, mut dashing, mut movement) in &mut query {
+        dashing.timer.tick(time.delta());
+
+        if dashing.timer.finished() {
+            movement.body.velocity.x = 0.0;
+            commands.entity(entity).remove::<Dashing>();
+            continue;
+        }
+
+        // The dash holds its height, so it can be used to cross gaps in the air
+        movement.body.velocity.x = DASH_SPEED * dashing.direction;
+        movement.body.velocity.y = movement.body.velocity.y.max(0.0);
+    }
+}
+
+fn spawn_afterimages(mut commands: Commands, time: Res<Time>,
+    mut query: Query<(&mut Dashing, &Transform, &Sprite, &Handle<Image>, &TextureAtlas)>) {
+
+    for (mut dashing, transform, sprite, texture, atlas) in &mut query {
+        dashing.afterimage_timer.tick(time.delta());
+
+        if !dashing.afterimage_timer.just_finished() {
+            continue;
+        }
+
+        commands.spawn((SpriteBundle {
+            texture: texture.clone(),
+            sprite: Sprite {
+                color: sprite.color.with_alpha(AFTERIMAGE_ALPHA),
+                ..sprite.clone()
+            },
+            // Drawn just behind the player
+            transform: transform.with_translation(transform.translation - Vec3::Z * 0.1),
+            ..default()
+        }, atlas.clone(), Afterimage {
+            timer: Timer::from_seconds(AFTERIMAGE_LIFETIME, TimerMode::Once)
+        }, CleanupGameStateExit));
+    }
+}
+
+fn fade_afterimages(mut commands: Commands, time: Res<Time>, mut query: Query<(Entity, &mut Afterimage, &mut Sprite)>) {
+    for (entity, mut afterimage, mut sprite) in &mut query {
+        afterimage.timer.tick(time.delta());
+
+        if afterimage.timer.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        sprite.color.set_alpha(AFTERIMAGE_ALPHA * afterimage.timer.fraction_remaining());
+    }
+}
@@ -1,42 +1,191 @@
+use::bevy::input::InputSystem;
 use::bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::GameState;
+use crate::controls::controls::{Action, ActionMap, AxisPolicy};
+use crate::replay::replay::ReplayPlayback;
+use crate::{GameState, PauseState};
 
 pub struct InputPlugin<GameState: States> {
     pub state: GameState
 }
 
+const GAMEPAD_DEADZONE: f32 = 0.2;
+
 impl Plugin for InputPlugin<GameState> {
     fn build(&self, app: &mut App) {
        app.add_event::<MovementInputEvent>();
-       app.add_systems(Update, movement_input.run_if(in_state(self.state.clone()))); 
+       app.init_resource::<HeldDirections>();
+       app.add_systems(OnEnter(GameState::Game), reset_held_directions);
+       app.add_systems(PreUpdate, movement_input.after(InputSystem)
+           .run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)).run_if(not(resource_exists::<ReplayPlayback>)));
     }
 }
 
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
 pub enum InputDirection {
     Left,
     Right,
     None
 }
 
+// `strength` goes from 0 to 1 and scales the player speed, only analog sticks send values below 1
+// `jump` and `dash` are whether their buttons are held this frame, presses and releases are found by the jump and dash logic
 #[derive(Event)]
 pub struct MovementInputEvent {
-    pub direction: InputDirection
+    pub direction: InputDirection,
+    pub strength: f32,
+    pub jump: bool,
+    pub dash: bool
+}
+
+// Held movement directions, oldest press first
+#[derive(Resource, Default)]
+pub struct HeldDirections {
+    order: Vec<InputDirection>
 }
 
-fn movement_input(mut ev_movement: EventWriter<MovementInputEvent>, input: Res<ButtonInput<KeyCode>>) {
-    
-    let mut input_direction = InputDirection::None;
+impl HeldDirections {
+    fn update(&mut self, left: bool, right: bool) {
+        for (direction, held) in [(InputDirection::Left, left), (InputDirection::Right, right)] {
+            let index = self.order.iter().position(|held_direction| *held_direction == direction);
 
-    for key in input.get_pressed() {
-        match key {
-            KeyCode::KeyA => input_direction = InputDirection::Left,
-            KeyCode::ArrowLeft => input_direction = InputDirection::Left,
-            KeyCode::KeyD => input_direction = InputDirection::Right,
-            KeyCode::ArrowRight => input_direction = InputDirection::Right,
-            _ => input_direction = InputDirection::None
+            match (held, index) {
+                (true, None) => self.order.push(direction),
+                (false, Some(index)) => { self.order.remove(index); },
+                _ => {}
+            }
         }
     }
+}
+
+fn held_directions(keys: impl Iterator<Item = KeyCode>, action_map: &ActionMap) -> (bool, bool) {
+    let mut left = false;
+    let mut right = false;
+
+    for key in keys {
+        left |= action_map.is_bound(Action::MoveLeft, key);
+        right |= action_map.is_bound(Action::MoveRight, key);
+    }
+
+    (left, right)
+}
+
+fn resolve_direction(held: &[InputDirection], policy: AxisPolicy) -> InputDirection {
+    match (held, policy) {
+        ([], _) => InputDirection::None,
+        ([only], _) => *only,
+        (_, AxisPolicy::LastPressed) => *held.last().unwrap(),
+        (_, AxisPolicy::FirstPressed) => held[0],
+        (_, AxisPolicy::CancelOut) => InputDirection::None,
+    }
+}
+
+fn reset_held_directions(mut held: ResMut<HeldDirections>) {
+    held.order.clear();
+}
+
+fn movement_input(mut ev_movement: EventWriter<MovementInputEvent>, input: Res<ButtonInput<KeyCode>>, action_map: Res<ActionMap>, mut held: ResMut<HeldDirections>,
+    gamepads: Res<Gamepads>, gamepad_buttons: Res<ButtonInput<GamepadButton>>, gamepad_axes: Res<Axis<GamepadAxis>>) {
+
+    let (left, right) = held_directions(input.get_pressed().copied(), &action_map);
+    held.update(left, right);
+
+    let jump = input.get_pressed().any(|key| action_map.is_bound(Action::Jump, *key)) || gamepad_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::South);
+    let dash = input.get_pressed().any(|key| action_map.is_bound(Action::Dash, *key)) || gamepad_pressed(&gamepads, &gamepad_buttons, GamepadButtonType::West);
+    let input_direction = resolve_direction(&held.order, action_map.axis_policy);
+
+    if input_direction != InputDirection::None {
+        ev_movement.send(MovementInputEvent { direction: input_direction, strength: 1.0, jump, dash });
+        return;
+    }
+
+    let (direction, strength) = gamepad_direction(&gamepads, &gamepad_buttons, &gamepad_axes);
+    ev_movement.send(MovementInputEvent { direction, strength, jump, dash });
+}
+
+fn gamepad_pressed(gamepads: &Gamepads, buttons: &ButtonInput<GamepadButton>, button: GamepadButtonType) -> bool {
+    gamepads.iter().any(|gamepad| buttons.pressed(GamepadButton::new(gamepad, button)))
+}
+
+fn gamepad_direction(gamepads: &Gamepads, buttons: &ButtonInput<GamepadButton>, axes: &Axis<GamepadAxis>) -> (InputDirection, f32) {
+    for gamepad in gamepads.iter() {
+        if buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadLeft)) {
+            return (InputDirection::Left, 1.0);
+        }
+
+        if buttons.pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadRight)) {
+            return (InputDirection::Right, 1.0);
+        }
 
-    ev_movement.send(MovementInputEvent { direction: input_direction });
-}
\ No newline at end of file
+        let stick_x = axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickX)).unwrap_or(0.0);
+
+        if stick_x.abs() > GAMEPAD_DEADZONE {
+            // Rescale so the speed starts from zero at the edge of the deadzone
+            let strength = ((stick_x.abs() - GAMEPAD_DEADZONE) / (1.0 - GAMEPAD_DEADZONE)).min(1.0);
+            let direction = if stick_x < 0.0 { InputDirection::Left } else { InputDirection::Right };
+
+            return (direction, strength);
+        }
+    }
+
+    (InputDirection::None, 0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unrelated_keys_are_ignored() {
+        let action_map = ActionMap::default();
+
+        assert_eq!(held_directions([KeyCode::ShiftLeft, KeyCode::KeyD].into_iter(), &action_map), (false, true));
+        assert_eq!(held_directions([KeyCode::KeyD, KeyCode::ControlLeft].into_iter(), &action_map), (false, true));
+        assert_eq!(held_directions([KeyCode::ShiftLeft].into_iter(), &action_map), (false, false));
+    }
+
+    #[test]
+    fn any_bound_key_holds_its_direction() {
+        let action_map = ActionMap::default();
+
+        assert_eq!(held_directions([KeyCode::KeyA, KeyCode::ArrowLeft].into_iter(), &action_map), (true, false));
+        assert_eq!(held_directions([KeyCode::ArrowLeft, KeyCode::KeyD].into_iter(), &action_map), (true, true));
+    }
+
+    #[test]
+    fn held_directions_keep_press_order() {
+        let mut held = HeldDirections::default();
+
+        held.update(false, true);
+        held.update(true, true);
+        assert_eq!(held.order, vec![InputDirection::Right, InputDirection::Left]);
+
+        held.update(true, false);
+        assert_eq!(held.order, vec![InputDirection::Left]);
+
+        held.update(true, true);
+        assert_eq!(held.order, vec![InputDirection::Left, InputDirection::Right]);
+
+        held.update(false, false);
+        assert!(held.order.is_empty());
+    }
+
+    #[test]
+    fn single_direction_wins_with_every_policy() {
+        for policy in [AxisPolicy::LastPressed, AxisPolicy::FirstPressed, AxisPolicy::CancelOut] {
+            assert_eq!(resolve_direction(&[], policy), InputDirection::None);
+            assert_eq!(resolve_direction(&[InputDirection::Left], policy), InputDirection::Left);
+            assert_eq!(resolve_direction(&[InputDirection::Right], policy), InputDirection::Right);
+        }
+    }
+
+    #[test]
+    fn both_directions_resolve_by_policy() {
+        let held = [InputDirection::Left, InputDirection::Right];
+
+        assert_eq!(resolve_direction(&held, AxisPolicy::LastPressed), InputDirection::Right);
+        assert_eq!(resolve_direction(&held, AxisPolicy::FirstPressed), InputDirection::Left);
+        assert_eq!(resolve_direction(&held, AxisPolicy::CancelOut), InputDirection::None);
+    }
+}
@@ -0,0 +1,100 @@
+use::bevy::prelude::*;
+
+use crate::movement::movement::{check_direction, Movement};
+use crate::{GameState, GameplaySet, PauseState};
+
+use super::player::Player;
+use super::player_input::MovementInputEvent;
+
+pub struct JumpPlugin<GameState: States> {
+    pub state: GameState
+}
+
+const JUMP_SPEED: f32 = 600.0;
+const AIR_JUMP_SPEED: f32 = 500.0;
+// Releasing the button while rising keeps only this part of the upward speed
+const JUMP_CUT: f32 = 0.5;
+const COYOTE_TIME: f32 = 0.1;
+const JUMP_BUFFER_TIME: f32 = 0.12;
+
+impl Plugin for JumpPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, jump.in_set(GameplaySet::Player).after(check_direction)
+            .run_if(in_state(self.state)).run_if(in_state(PauseState::Running)));
+    }
+}
+
+// `air_jumps` is how many extra jumps can be made before landing again, 0 disables the double jump
+#[derive(Component)]
+pub struct Jump {
+    pub air_jumps: u32,
+    air_jumps_left: u32,
+    coyote_time_left: f32,
+    buffer_time_left: f32,
+    was_held: bool,
+    rising: bool
+}
+
+impl Jump {
+    pub fn new(air_jumps: u32) -> Self {
+        Self {
+            air_jumps,
+            air_jumps_left: air_jumps,
+            coyote_time_left: 0.0,
+            buffer_time_left: 0.0,
+            was_held: false,
+            rising: false
+        }
+    }
+
+    pub fn is_grounded(movement: &Movement) -> bool {
+        movement.gravity.is_touching_terrain && movement.body.velocity.y <= 0.0
+    }
+}
+
+pub fn jump(time: Res<Time>, mut ev_movement: EventReader<MovementInputEvent>, mut query: Query<(&mut Jump, &mut Movement), With<Player>>) {
+    let Ok((mut jump, mut movement)) = query.get_single_mut() else {
+        return;
+    };
+
+    let held = ev_movement.read().last().is_some_and(|event| event.jump);
+    let just_pressed = held && !jump.was_held;
+    jump.was_held = held;
+
+    // Coyote time lets a jump start shortly after walking off an edge
+    if Jump::is_grounded(&movement) {
+        jump.coyote_time_left = COYOTE_TIME;
+        jump.air_jumps_left = jump.air_jumps;
+    } else {
+        jump.coyote_time_left = (jump.coyote_time_left - time.delta_seconds()).max(0.0);
+    }
+
+    // A press shortly before landing is kept and used as soon as the player can jump
+    if just_pressed {
+        jump.buffer_time_left = JUMP_BUFFER_TIME;
+    } else {
+        jump.buffer_time_left = (jump.buffer_time_left - time.delta_seconds()).max(0.0);
+    }
+
+    if jump.buffer_time_left > 0.0 && jump.coyote_time_left > 0.0 {
+        movement.body.velocity.y = JUMP_SPEED;
+        jump.buffer_time_left = 0.0;
+        jump.coyote_time_left = 0.0;
+        jump.rising = true;
+    } else if just_pressed && jump.air_jumps_left > 0 {
+        movement.body.velocity.y = AIR_JUMP_SPEED;
+        jump.air_jumps_left -= 1;
+        jump.buffer_time_left = 0.0;
+        jump.rising = true;
+    }
+
+    // Letting go early makes a shorter jump
+    if jump.rising && !held && movement.body.velocity.y > 0.0 {
+        movement.body.velocity.y *= JUMP_CUT;
+        jump.rising = false;
+    }
+
+    if movement.body.velocity.y <= 0.0 {
+        jump.rising = false;
+    }
+}
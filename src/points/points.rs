@@ -1,14 +1,207 @@
+use std::time::Duration;
+
 use::bevy::prelude::*;
 
-pub struct PointsPlugin;
+use crate::{CleanupGameStateExit, GameState, GameplaySet, PauseState};
+
+pub struct PointsPlugin<GameState: States> {
+    pub state: GameState
+}
 
-impl Plugin for PointsPlugin {
+// Seconds to get the next combo pickup before the combo is lost
+const COMBO_WINDOW: f32 = 2.5;
+// Combo pickups needed for every step of the combo multiplier
+const COMBO_STEP: u32 = 3;
+const MAX_COMBO_MULTIPLIER: i32 = 5;
+const POPUP_LIFETIME: f32 = 0.8;
+const POPUP_RISE_SPEED: f32 = 60.0;
+const POPUP_COLOR: Color = Color::srgb(1.0, 0.9, 0.3);
+
+impl Plugin for PointsPlugin<GameState> {
     fn build(&self, app: &mut App) {
+        app.add_event::<ScoreEvent>();
         app.insert_resource(Points { value: 0 });
+        app.init_resource::<ScoreMultiplier>();
+        app.init_resource::<Combo>();
+        app.add_systems(OnEnter(GameState::Game), reset_combo);
+        app.add_systems(Update, (tick_combo, add_scored_points, float_popups).chain().in_set(GameplaySet::Score).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
 #[derive(Resource)]
 pub struct Points {
     pub value: i32
-}
\ No newline at end of file
+}
+
+// Everything that gives points sends this, so multipliers apply the same way to all of them
+// `position` shows the points where they were made, `combo` extends the combo
+#[derive(Event)]
+pub struct ScoreEvent {
+    pub points: i32,
+    pub position: Option<Vec3>,
+    pub combo: bool
+}
+
+impl ScoreEvent {
+    pub fn new(points: i32) -> Self {
+        Self { points, position: None, combo: false }
+    }
+
+    // Points for a pickup or a stunt, they grow the combo and pop up at `position`
+    pub fn combo_at(points: i32, position: Vec3) -> Self {
+        Self { points, position: Some(position), combo: true }
+    }
+}
+
+#[derive(Resource)]
+pub struct ScoreMultiplier {
+    pub value: i32
+}
+
+impl Default for ScoreMultiplier {
+    fn default() -> Self {
+        Self { value: 1 }
+    }
+}
+
+// Consecutive combo pickups, each one has to come before the window runs out
+#[derive(Resource)]
+pub struct Combo {
+    pub count: u32,
+    window: Timer
+}
+
+impl Default for Combo {
+    fn default() -> Self {
+        Self { count: 0, window: Timer::from_seconds(COMBO_WINDOW, TimerMode::Once) }
+    }
+}
+
+impl Combo {
+    pub fn multiplier(&self) -> i32 {
+        (1 + (self.count / COMBO_STEP) as i32).min(MAX_COMBO_MULTIPLIER)
+    }
+
+    fn extend(&mut self) {
+        self.count += 1;
+        self.window.reset();
+    }
+
+    fn tick(&mut self, delta: Duration) {
+        self.window.tick(delta);
+
+        if self.window.just_finished() {
+            self.count = 0;
+        }
+    }
+}
+
+#[derive(Component)]
+struct ScorePopup {
+    lifetime: Timer
+}
+
+fn reset_combo(mut combo: ResMut<Combo>) {
+    *combo = Combo::default();
+}
+
+fn tick_combo(time: Res<Time>, mut combo: ResMut<Combo>) {
+    combo.tick(time.delta());
+}
+
+fn add_scored_points(mut commands: Commands, mut ev_score: EventReader<ScoreEvent>, multiplier: Res<ScoreMultiplier>, mut combo: ResMut<Combo>, mut points: ResMut<Points>) {
+    for event in ev_score.read() {
+        if event.combo {
+            combo.extend();
+        }
+
+        let total_multiplier = multiplier.value * combo.multiplier();
+        let scored = event.points * total_multiplier;
+        points.value += scored;
+
+        if let Some(position) = event.position {
+            let label = if total_multiplier > 1 { format!("+{} x{}", scored, total_multiplier) } else { format!("+{}", scored) };
+
+            commands.spawn((Text2dBundle {
+                text: Text::from_section(label, TextStyle {
+                    font_size: 28.0,
+                    color: POPUP_COLOR,
+                    ..default()
+                }),
+                transform: Transform::from_translation(position.truncate().extend(5.0)),
+                ..default()
+            }, ScorePopup {
+                lifetime: Timer::from_seconds(POPUP_LIFETIME, TimerMode::Once)
+            }, CleanupGameStateExit));
+        }
+    }
+}
+
+// Popups drift up and fade out
+fn float_popups(mut commands: Commands, time: Res<Time>, mut popup_query: Query<(Entity, &mut ScorePopup, &mut Transform, &mut Text)>) {
+    for (entity, mut popup, mut transform, mut text) in &mut popup_query {
+        popup.lifetime.tick(time.delta());
+
+        if popup.lifetime.finished() {
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        transform.translation.y += POPUP_RISE_SPEED * time.delta_seconds();
+
+        for section in &mut text.sections {
+            section.style.color = POPUP_COLOR.with_alpha(1.0 - popup.lifetime.fraction());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn combo_of(count: u32) -> Combo {
+        Combo { count, ..default() }
+    }
+
+    #[test]
+    fn multiplier_rises_every_combo_step() {
+        assert_eq!(combo_of(0).multiplier(), 1);
+        assert_eq!(combo_of(2).multiplier(), 1);
+        assert_eq!(combo_of(3).multiplier(), 2);
+        assert_eq!(combo_of(8).multiplier(), 3);
+        assert_eq!(combo_of(12).multiplier(), 5);
+    }
+
+    #[test]
+    fn multiplier_stops_at_the_maximum() {
+        assert_eq!(combo_of(15).multiplier(), MAX_COMBO_MULTIPLIER);
+        assert_eq!(combo_of(1000).multiplier(), MAX_COMBO_MULTIPLIER);
+    }
+
+    #[test]
+    fn pickups_inside_the_window_keep_the_combo() {
+        let mut combo = Combo::default();
+
+        for _ in 0..4 {
+            combo.extend();
+            combo.tick(Duration::from_secs_f32(COMBO_WINDOW * 0.9));
+        }
+
+        assert_eq!(combo.count, 4);
+        assert_eq!(combo.multiplier(), 2);
+    }
+
+    #[test]
+    fn combo_is_lost_when_the_window_runs_out() {
+        let mut combo = Combo::default();
+        combo.extend();
+        combo.extend();
+
+        combo.tick(Duration::from_secs_f32(COMBO_WINDOW * 0.5));
+        assert_eq!(combo.count, 2);
+
+        combo.tick(Duration::from_secs_f32(COMBO_WINDOW * 0.5));
+        assert_eq!(combo.count, 0);
+        assert_eq!(combo.multiplier(), 1);
+    }
+}
@@ -0,0 +1 @@
+pub mod powerups;
\ No newline at end of file
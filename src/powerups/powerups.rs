@@ -0,0 +1,147 @@
+use::bevy::prelude::*;
+use bevy::utils::HashMap;
+
+use crate::coin::coin::{Coin, PowerUpCollectedEvent};
+use crate::gravity::gravity::apply_gravity;
+use crate::knife::knife::Knife;
+use crate::movement::movement::{Movement, PhysicsSet};
+use crate::player::player::Player;
+use crate::points::points::ScoreMultiplier;
+use crate::{GameState, GameplaySet, PauseState};
+
+pub struct PowerUpsPlugin<GameState: States> {
+    pub state: GameState
+}
+
+// How fast knives move while slow motion is active
+const SLOW_MOTION_KNIFE_TIME_SCALE: f32 = 0.4;
+const MAGNET_RADIUS: f32 = 350.0;
+const MAGNET_SPEED: f32 = 500.0;
+const DOUBLE_POINTS_MULTIPLIER: i32 = 2;
+
+impl Plugin for PowerUpsPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ActivePowerUps>();
+        app.add_systems(OnEnter(GameState::Game), reset_power_ups);
+        app.add_systems(Update, (activate_power_ups, tick_power_ups, slow_down_knives, apply_score_multiplier).chain().in_set(GameplaySet::PowerUps).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+        app.add_systems(FixedUpdate, pull_coins_to_player.in_set(PhysicsSet::Forces).after(apply_gravity).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Hash)]
+pub enum PowerUp {
+    // Absorbs the next knife hit
+    Shield,
+    // Knives move slower
+    SlowMotion,
+    // Coins fly to the player
+    Magnet,
+    DoublePoints
+}
+
+impl PowerUp {
+    pub const ALL: [PowerUp; 4] = [PowerUp::Shield, PowerUp::SlowMotion, PowerUp::Magnet, PowerUp::DoublePoints];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PowerUp::Shield => "Shield",
+            PowerUp::SlowMotion => "Slow Motion",
+            PowerUp::Magnet => "Magnet",
+            PowerUp::DoublePoints => "Double Points"
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PowerUp::Shield => Color::srgb(0.3, 0.8, 1.0),
+            PowerUp::SlowMotion => Color::srgb(0.7, 0.4, 1.0),
+            PowerUp::Magnet => Color::srgb(1.0, 0.35, 0.35),
+            PowerUp::DoublePoints => Color::srgb(0.4, 1.0, 0.4)
+        }
+    }
+
+    // Seconds the effect lasts, the shield also ends when it absorbs a hit
+    fn duration(&self) -> f32 {
+        match self {
+            PowerUp::Shield => 10.0,
+            PowerUp::SlowMotion => 5.0,
+            PowerUp::Magnet => 8.0,
+            PowerUp::DoublePoints => 10.0
+        }
+    }
+}
+
+// Time left on every active effect, picking up an active one again restarts its timer
+#[derive(Resource, Default)]
+pub struct ActivePowerUps {
+    timers: HashMap<PowerUp, Timer>
+}
+
+impl ActivePowerUps {
+    pub fn is_active(&self, power_up: PowerUp) -> bool {
+        self.timers.contains_key(&power_up)
+    }
+
+    pub fn remaining(&self, power_up: PowerUp) -> Option<f32> {
+        self.timers.get(&power_up).map(|timer| timer.remaining_secs())
+    }
+
+    // Ends the effect early, returns whether it was active
+    pub fn consume(&mut self, power_up: PowerUp) -> bool {
+        self.timers.remove(&power_up).is_some()
+    }
+
+    fn activate(&mut self, power_up: PowerUp) {
+        self.timers.insert(power_up, Timer::from_seconds(power_up.duration(), TimerMode::Once));
+    }
+}
+
+fn reset_power_ups(mut power_ups: ResMut<ActivePowerUps>, mut multiplier: ResMut<ScoreMultiplier>) {
+    *power_ups = ActivePowerUps::default();
+    *multiplier = ScoreMultiplier::default();
+}
+
+fn activate_power_ups(mut ev_power_up: EventReader<PowerUpCollectedEvent>, mut power_ups: ResMut<ActivePowerUps>) {
+    for event in ev_power_up.read() {
+        power_ups.activate(event.power_up);
+    }
+}
+
+fn tick_power_ups(time: Res<Time>, mut power_ups: ResMut<ActivePowerUps>) {
+    for timer in power_ups.timers.values_mut() {
+        timer.tick(time.delta());
+    }
+
+    power_ups.timers.retain(|_, timer| !timer.finished());
+}
+
+fn slow_down_knives(power_ups: Res<ActivePowerUps>, mut knife_query: Query<&mut Movement, With<Knife>>) {
+    let time_scale = if power_ups.is_active(PowerUp::SlowMotion) { SLOW_MOTION_KNIFE_TIME_SCALE } else { 1.0 };
+
+    for mut movement in &mut knife_query {
+        movement.body.time_scale = time_scale;
+    }
+}
+
+fn apply_score_multiplier(power_ups: Res<ActivePowerUps>, mut multiplier: ResMut<ScoreMultiplier>) {
+    multiplier.value = if power_ups.is_active(PowerUp::DoublePoints) { DOUBLE_POINTS_MULTIPLIER } else { 1 };
+}
+
+// Coins close enough fly straight at the player, ignoring gravity
+fn pull_coins_to_player(power_ups: Res<ActivePowerUps>, mut coin_query: Query<(&mut Movement, &Transform), With<Coin>>, player_query: Query<&Transform, With<Player>>) {
+    if !power_ups.is_active(PowerUp::Magnet) {
+        return;
+    }
+
+    let Ok(player_transform) = player_query.get_single() else {
+        return;
+    };
+
+    for (mut movement, transform) in &mut coin_query {
+        let offset = (player_transform.translation - transform.translation).truncate();
+
+        if offset.length() <= MAGNET_RADIUS {
+            movement.body.velocity = offset.normalize_or_zero() * MAGNET_SPEED;
+        }
+    }
+}
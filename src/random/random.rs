@@ -0,0 +1,60 @@
+use std::collections::HashMap;
+
+use::bevy::prelude::*;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::{options::options::LaunchOptions, replay::replay::ReplayPlayback, GameState};
+
+pub struct RandomPlugin;
+
+impl Plugin for RandomPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SeedSelection>();
+        app.insert_resource(GameRng::new(0));
+        app.add_systems(Startup, select_seed_from_options);
+        app.add_systems(OnEnter(GameState::Game), reseed_game_rng);
+    }
+}
+
+// Every consumer draws from its own stream, so the order in which systems run never changes the numbers they get
+// ChaCha8 gives the same numbers on every platform and rand version, unlike `StdRng`
+#[derive(Clone, Copy, Eq, PartialEq, Hash)]
+pub enum RngStream {
+    Knives,
+    Coins,
+    Waves
+}
+
+#[derive(Resource, Default)]
+pub struct SeedSelection {
+    pub seed: Option<u64>
+}
+
+#[derive(Resource)]
+pub struct GameRng {
+    pub seed: u64,
+    streams: HashMap<RngStream, ChaCha8Rng>
+}
+
+impl GameRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed, streams: HashMap::new() }
+    }
+
+    pub fn stream(&mut self, stream: RngStream) -> &mut ChaCha8Rng {
+        let seed = self.seed;
+        self.streams.entry(stream).or_insert_with(|| ChaCha8Rng::seed_from_u64(seed ^ (stream as u64 + 1).wrapping_mul(0x9E37_79B9_7F4A_7C15)))
+    }
+}
+
+fn select_seed_from_options(options: Res<LaunchOptions>, mut seed_selection: ResMut<SeedSelection>) {
+    if options.seed.is_some() {
+        seed_selection.seed = options.seed;
+    }
+}
+
+fn reseed_game_rng(seed_selection: Res<SeedSelection>, playback: Option<Res<ReplayPlayback>>, mut game_rng: ResMut<GameRng>) {
+    let seed = playback.map(|playback| playback.seed()).or(seed_selection.seed).unwrap_or_else(|| rand::thread_rng().gen());
+    *game_rng = GameRng::new(seed);
+}
@@ -0,0 +1 @@
+pub mod replay;
\ No newline at end of file
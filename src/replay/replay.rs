@@ -0,0 +1,304 @@
+use std::time::Duration;
+
+use::bevy::prelude::*;
+use bevy::time::{TimeSystem, TimeUpdateStrategy};
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::difficulty::{ActiveDifficulty, Difficulty, DifficultyProfile};
+use crate::options::options::{is_headless, LaunchOptions};
+use crate::player::player_input::{InputDirection, MovementInputEvent};
+use crate::random::random::GameRng;
+use crate::settings::settings::RunSettings;
+use crate::storage::storage::{self, LoadError};
+use crate::terrain::terrain::ArenaChoice;
+use crate::{GameState, PauseState};
+
+pub struct ReplayPlugin;
+
+const REPLAY_VERSION: u32 = 10;
+const LAST_REPLAY_FILE: &str = "last_replay.ron";
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ReplayRecorder>();
+        app.add_systems(PostStartup, load_replay_from_options);
+        app.add_systems(OnEnter(GameState::Game), (start_recording, rewind_playback));
+        // Headless runs are for bots and CI, they would overwrite the last run a player actually played
+        app.add_systems(OnEnter(GameState::GameOver), save_recording.run_if(not(resource_exists::<ReplayPlayback>)).run_if(not(is_headless)));
+        // Paused frames are recorded too, the fixed timestep used up their time even though nothing moved
+        app.add_systems(PreUpdate, play_back_frame.run_if(in_state(GameState::Game)).run_if(resource_exists::<ReplayPlayback>));
+        app.add_systems(PostUpdate, (record_frame.run_if(not(resource_exists::<ReplayPlayback>)), advance_playback.run_if(resource_exists::<ReplayPlayback>))
+            .run_if(in_state(GameState::Game)));
+        app.add_systems(First, follow_recorded_time.before(TimeSystem).run_if(resource_exists::<ReplayPlayback>));
+        app.add_systems(Last, drive_playback_time.run_if(resource_exists::<ReplayPlayback>));
+    }
+}
+
+// `delta_nanos` is the virtual time the frame simulated, zero while paused and shortened by slow motion
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct ReplayFrame {
+    pub delta_nanos: u64,
+    pub paused: bool,
+    pub direction: InputDirection,
+    pub strength: f32,
+    pub jump: bool,
+    pub dash: bool
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    // Settings that change how the run plays out, the run plays with them instead of the local ones
+    pub expert_mode: bool,
+    pub stuck_knives: bool,
+    pub difficulty: Difficulty,
+    // The profile the run was played with, a custom one only exists on the machine that recorded it
+    pub difficulty_profile: DifficultyProfile,
+    pub arena: ArenaChoice,
+    pub frames: Vec<ReplayFrame>
+}
+
+#[derive(Resource, Default)]
+struct ReplayRecorder {
+    frames: Vec<ReplayFrame>
+}
+
+#[derive(Resource)]
+pub struct ReplayPlayback {
+    replay: Replay,
+    cursor: usize
+}
+
+impl ReplayPlayback {
+    pub fn seed(&self) -> u64 {
+        self.replay.seed
+    }
+
+    pub fn run_settings(&self) -> RunSettings {
+        RunSettings { expert_mode: self.replay.expert_mode, stuck_knives: self.replay.stuck_knives, difficulty: self.replay.difficulty, arena: self.replay.arena }
+    }
+
+    pub fn difficulty_profile(&self) -> &DifficultyProfile {
+        &self.replay.difficulty_profile
+    }
+
+    fn next_delta(&self) -> Duration {
+        let frame = self.replay.frames.get(self.cursor).or(self.replay.frames.first());
+        Duration::from_nanos(frame.map_or(0, |frame| frame.delta_nanos))
+    }
+}
+
+// The seed and settings of the replay stay in `ReplayPlayback`, the run reads them from there when it starts
+fn load_replay_from_options(mut commands: Commands, options: Res<LaunchOptions>) {
+    let Some(path) = &options.replay else {
+        return;
+    };
+
+    match storage::load_ron_file::<Replay>(path) {
+        Ok(replay) if replay.version == REPLAY_VERSION => {
+            info!("Playing back replay {} with seed {} ({} frames)", path.display(), replay.seed, replay.frames.len());
+            commands.insert_resource(ReplayPlayback { replay, cursor: 0 });
+        },
+        Ok(replay) => error!("Replay {} has unsupported version {}", path.display(), replay.version),
+        Err(LoadError::Missing) => error!("Replay {} does not exist", path.display()),
+        Err(LoadError::Io(error)) => error!("Could not read replay {}: {error}", path.display()),
+        Err(LoadError::Corrupt(error)) => error!("Replay {} is corrupt: {error}", path.display())
+    }
+}
+
+fn start_recording(mut recorder: ResMut<ReplayRecorder>) {
+    recorder.frames.clear();
+}
+
+// Frames are recorded after gameplay ran, with the time step and the input that frame actually used
+fn record_frame(time: Res<Time<Virtual>>, pause_state: Res<State<PauseState>>, mut ev_movement: EventReader<MovementInputEvent>, mut recorder: ResMut<ReplayRecorder>) {
+    let (direction, strength, jump, dash) = ev_movement.read().last()
+        .map_or((InputDirection::None, 0.0, false, false), |event| (event.direction, event.strength, event.jump, event.dash));
+
+    recorder.frames.push(ReplayFrame {
+        delta_nanos: time.delta().as_nanos() as u64,
+        paused: *pause_state.get() == PauseState::Paused,
+        direction,
+        strength,
+        jump,
+        dash
+    });
+}
+
+fn save_recording(recorder: Res<ReplayRecorder>, game_rng: Res<GameRng>, settings: Res<RunSettings>, difficulty: Res<ActiveDifficulty>) {
+    let replay = Replay {
+        version: REPLAY_VERSION,
+        seed: game_rng.seed,
+        expert_mode: settings.expert_mode,
+        stuck_knives: settings.stuck_knives,
+        difficulty: settings.difficulty,
+        difficulty_profile: difficulty.profile.clone(),
+        arena: settings.arena,
+        frames: recorder.frames.clone()
+    };
+
+    match storage::save_ron(LAST_REPLAY_FILE, &replay) {
+        Ok(()) => info!("Saved replay to {}", storage::data_dir().join(LAST_REPLAY_FILE).display()),
+        Err(error) => warn!("Could not save replay: {error}")
+    }
+}
+
+fn rewind_playback(playback: Option<ResMut<ReplayPlayback>>) {
+    if let Some(mut playback) = playback {
+        playback.cursor = 0;
+    }
+}
+
+// Runs before the state transitions, so the frame pauses or resumes just like the recorded one did
+// The pause key is ignored, only the recorded pauses happen
+fn play_back_frame(playback: Res<ReplayPlayback>, pause_state: Res<State<PauseState>>, mut next_pause_state: ResMut<NextState<PauseState>>,
+    mut ev_movement: EventWriter<MovementInputEvent>) {
+
+    let frame = playback.replay.frames.get(playback.cursor);
+    let recorded_state = if frame.is_some_and(|frame| frame.paused) { PauseState::Paused } else { PauseState::Running };
+
+    if *pause_state.get() == recorded_state {
+        next_pause_state.reset();
+    } else {
+        next_pause_state.set(recorded_state);
+    }
+
+    if recorded_state == PauseState::Paused {
+        return;
+    }
+
+    let (direction, strength, jump, dash) = frame
+        .map_or((InputDirection::None, 0.0, false, false), |frame| (frame.direction, frame.strength, frame.jump, frame.dash));
+    ev_movement.send(MovementInputEvent { direction, strength, jump, dash });
+}
+
+fn advance_playback(mut playback: ResMut<ReplayPlayback>) {
+    playback.cursor += 1;
+}
+
+// The recorded steps already contain the pauses and the slow motion, so virtual time has to pass them on unchanged
+fn follow_recorded_time(mut virtual_time: ResMut<Time<Virtual>>) {
+    virtual_time.unpause();
+    virtual_time.set_relative_speed(1.0);
+}
+
+// The time step of the next frame has to be known before it starts, so it is set at the very end of the current one
+fn drive_playback_time(playback: Res<ReplayPlayback>, game_state: Res<State<GameState>>, mut time_update_strategy: ResMut<TimeUpdateStrategy>) {
+    let delta = match game_state.get() {
+        GameState::Game => playback.next_delta(),
+        _ => Duration::from_nanos(playback.replay.frames.first().map_or(0, |frame| frame.delta_nanos))
+    };
+
+    *time_update_strategy = TimeUpdateStrategy::ManualDuration(delta);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::state::app::StatesPlugin;
+    use bevy::time::TimePlugin;
+
+    use super::*;
+
+    const FRAMES: usize = 60;
+    const DELTAS_MS: [u64; 3] = [16, 21, 9];
+
+    #[derive(Component, Default)]
+    struct Walker {
+        x: f32,
+        speed: f32
+    }
+
+    fn steer(mut ev_movement: EventReader<MovementInputEvent>, mut walker_query: Query<&mut Walker>) {
+        for event in ev_movement.read() {
+            for mut walker in &mut walker_query {
+                walker.speed = event.strength * 100.0;
+            }
+        }
+    }
+
+    fn walk(time: Res<Time>, mut walker_query: Query<&mut Walker>) {
+        for mut walker in &mut walker_query {
+            walker.x += walker.speed * time.delta_seconds();
+        }
+    }
+
+    fn press_keys(mut frame: Local<u32>, mut ev_movement: EventWriter<MovementInputEvent>) {
+        *frame += 1;
+        ev_movement.send(MovementInputEvent { direction: InputDirection::Right, strength: (*frame % 4) as f32 * 0.25, jump: false, dash: false });
+    }
+
+    // A run with the pause handling of the game and a walker moving on the fixed timestep
+    fn game_app() -> App {
+        let mut app = App::new();
+        app.add_plugins((TimePlugin, StatesPlugin));
+        app.insert_state(GameState::Game);
+        app.add_sub_state::<PauseState>();
+        app.add_event::<MovementInputEvent>();
+        app.add_systems(OnEnter(PauseState::Paused), crate::pause_time);
+        app.add_systems(OnExit(PauseState::Paused), crate::resume_time);
+        app.add_systems(FixedUpdate, walk.run_if(in_state(PauseState::Running)));
+        app.world_mut().spawn(Walker::default());
+        app
+    }
+
+    fn walker_x(app: &mut App) -> f32 {
+        app.world_mut().query::<&Walker>().single(app.world()).x
+    }
+
+    #[test]
+    fn playback_matches_a_run_with_a_pause_and_slow_motion() {
+        let mut live = game_app();
+        live.init_resource::<ReplayRecorder>();
+        live.add_systems(Update, (press_keys, steer).chain().run_if(in_state(PauseState::Running)));
+        live.add_systems(PostUpdate, record_frame);
+
+        let mut live_positions = Vec::new();
+
+        for frame in 0..FRAMES {
+            live.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(DELTAS_MS[frame % DELTAS_MS.len()])));
+
+            match frame {
+                15 => live.world_mut().resource_mut::<NextState<PauseState>>().set(PauseState::Paused),
+                30 => live.world_mut().resource_mut::<NextState<PauseState>>().set(PauseState::Running),
+                40 => live.world_mut().resource_mut::<Time<Virtual>>().set_relative_speed(0.3),
+                50 => live.world_mut().resource_mut::<Time<Virtual>>().set_relative_speed(1.0),
+                _ => {}
+            }
+
+            live.update();
+            live_positions.push(walker_x(&mut live));
+        }
+
+        let frames = live.world_mut().remove_resource::<ReplayRecorder>().unwrap().frames;
+        assert!(frames.iter().any(|frame| frame.paused));
+        assert!(live_positions[20] == live_positions[25]);
+
+        let mut playback = game_app();
+        playback.insert_resource(ReplayPlayback {
+            replay: Replay {
+                version: REPLAY_VERSION,
+                seed: 0,
+                expert_mode: false,
+                stuck_knives: false,
+                difficulty: Difficulty::Normal,
+                difficulty_profile: DifficultyProfile::default(),
+                arena: ArenaChoice::Classic,
+                frames
+            },
+            cursor: 0
+        });
+        playback.add_systems(Update, steer.run_if(in_state(PauseState::Running)));
+        playback.add_systems(PreUpdate, play_back_frame);
+        playback.add_systems(PostUpdate, advance_playback);
+        playback.add_systems(First, follow_recorded_time.before(TimeSystem));
+        playback.add_systems(Last, drive_playback_time);
+
+        let playback_positions: Vec<f32> = (0..FRAMES).map(|_| {
+            playback.update();
+            walker_x(&mut playback)
+        }).collect();
+
+        assert_eq!(playback_positions, live_positions);
+    }
+}
@@ -0,0 +1,97 @@
+use::bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::difficulty::difficulty::Difficulty;
+use crate::replay::replay::ReplayPlayback;
+use crate::storage::storage::{self, LoadError};
+use crate::terrain::terrain::ArenaChoice;
+use crate::GameState;
+
+pub struct SettingsPlugin;
+
+const SETTINGS_FILE: &str = "settings.ron";
+const SETTINGS_VERSION: u32 = 1;
+
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(GameSettings::load());
+        app.init_resource::<RunSettings>();
+        app.add_systems(OnEnter(GameState::Game), pick_run_settings);
+    }
+}
+
+// Gameplay options picked on the settings screen, new fields need `#[serde(default)]` so older files still load
+#[derive(Resource, Serialize, Deserialize)]
+pub struct GameSettings {
+    pub version: u32,
+    // Knives appear without a warning marker on the ground first
+    #[serde(default)]
+    pub expert_mode: bool,
+    // Landed knives stay in the ground for a while and hurt when stepped on
+    #[serde(default)]
+    pub stuck_knives: bool,
+    #[serde(default)]
+    pub difficulty: Difficulty,
+    #[serde(default)]
+    pub arena: ArenaChoice
+}
+
+// The settings the current run plays with, picked when it starts
+// A replay brings the ones it was recorded with, they never end up in the saved settings
+#[derive(Resource, Clone, Copy, Default)]
+pub struct RunSettings {
+    pub expert_mode: bool,
+    pub stuck_knives: bool,
+    pub difficulty: Difficulty,
+    pub arena: ArenaChoice
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            expert_mode: false,
+            stuck_knives: false,
+            difficulty: Difficulty::default(),
+            arena: ArenaChoice::default()
+        }
+    }
+}
+
+impl GameSettings {
+    pub fn load() -> Self {
+        match storage::load_ron::<GameSettings>(SETTINGS_FILE) {
+            Ok(settings) if settings.version == SETTINGS_VERSION => settings,
+            Ok(settings) => {
+                warn!("Settings file has unsupported version {}, using the default settings", settings.version);
+                Self::default()
+            },
+            Err(LoadError::Missing) => Self::default(),
+            Err(LoadError::Corrupt(error)) => {
+                warn!("Settings file is corrupt ({error}), using the default settings");
+                if let Err(error) = storage::backup_corrupt(SETTINGS_FILE) {
+                    warn!("Could not back up settings file: {error}");
+                }
+                Self::default()
+            },
+            Err(LoadError::Io(error)) => {
+                warn!("Could not read settings file: {error}");
+                Self::default()
+            }
+        }
+    }
+
+    pub fn run_settings(&self) -> RunSettings {
+        RunSettings { expert_mode: self.expert_mode, stuck_knives: self.stuck_knives, difficulty: self.difficulty, arena: self.arena }
+    }
+
+    pub fn save(&self) {
+        if let Err(error) = storage::save_ron(SETTINGS_FILE, self) {
+            warn!("Could not save settings file: {error}");
+        }
+    }
+}
+
+pub fn pick_run_settings(settings: Res<GameSettings>, playback: Option<Res<ReplayPlayback>>, mut run_settings: ResMut<RunSettings>) {
+    *run_settings = playback.map_or_else(|| settings.run_settings(), |playback| playback.run_settings());
+}
@@ -0,0 +1,49 @@
+use::bevy::prelude::*;
+
+use crate::{coin::coin::CoinTouchedEvent, knife::{knife::KnifeLandedEvent, knife_near_miss::NearMissEvent}, GameState, GameplaySet, PauseState};
+
+pub struct RunStatsPlugin<GameState: States> {
+    pub state: GameState
+}
+
+impl Plugin for RunStatsPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>();
+        app.add_systems(OnEnter(GameState::Game), reset_run_stats);
+        app.add_systems(Update, (tick_run_time, count_coins, count_dodged_knives, count_near_misses).chain().in_set(GameplaySet::Score).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
+    }
+}
+
+#[derive(Resource, Default)]
+pub struct RunStats {
+    pub coins_collected: i32,
+    pub knives_dodged: i32,
+    pub near_misses: i32,
+    pub run_time: f32
+}
+
+fn reset_run_stats(mut run_stats: ResMut<RunStats>) {
+    *run_stats = RunStats::default();
+}
+
+fn tick_run_time(time: Res<Time>, mut run_stats: ResMut<RunStats>) {
+    run_stats.run_time += time.delta_seconds();
+}
+
+fn count_coins(mut ev_coin_collected: EventReader<CoinTouchedEvent>, mut run_stats: ResMut<RunStats>) {
+    for _ in ev_coin_collected.read() {
+        run_stats.coins_collected += 1;
+    }
+}
+
+fn count_dodged_knives(mut ev_knife_landed: EventReader<KnifeLandedEvent>, mut run_stats: ResMut<RunStats>) {
+    for _ in ev_knife_landed.read() {
+        run_stats.knives_dodged += 1;
+    }
+}
+
+fn count_near_misses(mut ev_near_miss: EventReader<NearMissEvent>, mut run_stats: ResMut<RunStats>) {
+    for _ in ev_near_miss.read() {
+        run_stats.near_misses += 1;
+    }
+}
@@ -0,0 +1 @@
+pub mod storage;
\ No newline at end of file
@@ -0,0 +1,50 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+const APP_DIR: &str = "avoid-the-knifes";
+
+pub enum LoadError {
+    Missing,
+    Io(io::Error),
+    Corrupt(ron::error::SpannedError)
+}
+
+pub fn data_dir() -> PathBuf {
+    dirs::data_dir().unwrap_or_else(|| PathBuf::from(".")).join(APP_DIR)
+}
+
+pub fn load_ron<T: DeserializeOwned>(file_name: &str) -> Result<T, LoadError> {
+    load_ron_file(&data_dir().join(file_name))
+}
+
+pub fn load_ron_file<T: DeserializeOwned>(path: &Path) -> Result<T, LoadError> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Err(LoadError::Missing),
+        Err(error) => return Err(LoadError::Io(error))
+    };
+
+    ron::from_str(&contents).map_err(LoadError::Corrupt)
+}
+
+pub fn save_ron<T: Serialize>(file_name: &str, value: &T) -> io::Result<()> {
+    let dir = data_dir();
+    fs::create_dir_all(&dir)?;
+
+    let contents = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default())
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+
+    // Write to a temporary file first so a crash never leaves a half written file behind
+    let temp_path = dir.join(format!("{file_name}.tmp"));
+    fs::write(&temp_path, contents)?;
+    fs::rename(temp_path, dir.join(file_name))
+}
+
+pub fn backup_corrupt(file_name: &str) -> io::Result<()> {
+    let dir = data_dir();
+    fs::rename(dir.join(file_name), dir.join(format!("{file_name}.corrupt")))
+}
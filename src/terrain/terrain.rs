@@ -1,39 +1,304 @@
+use std::fmt;
+use std::io;
+
+use::bevy::asset::io::Reader;
+use::bevy::asset::{AssetLoader, AsyncReadExt, LoadContext};
 use bevy::prelude::*;
+use bevy_asset_loader::asset_collection::AssetCollection;
+use serde::{Deserialize, Serialize};
 
-use crate::{CleanupGameStateExit, GameState};
+use crate::collision::collision::{Collider, CollisionLayer, CollisionLayers};
+use crate::movement::movement::Movement;
+use crate::player::player::Player;
+use crate::settings::settings::{pick_run_settings, RunSettings};
+use crate::{CleanupGameStateExit, GameState, GameplaySet, PauseState, PlayField};
 
 pub struct TerrainPlugin<GameState: States> {
     pub state: GameState
 }
 
 const TERRAIN_SPRITE_PATH: &str = "sprites/terrain.png";
+// Platforms are built from square tiles of this many pixels
+const TILE_SIZE: f32 = 40.0;
+const ONE_WAY_TINT: Color = Color::srgb(0.65, 0.8, 1.0);
+// Things this far below the play field have fallen through a gap and are removed
+const FALL_OUT_MARGIN: f32 = 100.0;
 
 impl Plugin for TerrainPlugin<GameState> {
     fn build(&self, app: &mut App) {
-        app.add_systems(OnEnter(GameState::Game), spawn_terrain.run_if(in_state(self.state.clone())));
+        app.init_asset::<Arena>();
+        app.init_asset_loader::<ArenaLoader>();
+        app.init_resource::<ActiveArena>();
+        app.add_systems(OnEnter(GameState::Game), (apply_arena.after(pick_run_settings), spawn_terrain.run_if(in_state(self.state.clone()))).chain());
+        app.add_systems(Update, despawn_fallen.in_set(GameplaySet::World).run_if(in_state(self.state.clone())).run_if(in_state(PauseState::Running)));
     }
 }
 
+// One way platforms only hold things landing on them from above, everything else passes through
 #[derive(Component)]
-pub struct Terrain;
-
-fn spawn_terrain(mut commands: Commands, asset_server: Res<AssetServer>, window_query: Query<&Window>) {
-
-    let window = window_query.get_single().unwrap();
-
-    commands.spawn(( 
-            SpriteBundle {
-                texture: asset_server.load(TERRAIN_SPRITE_PATH),
-                sprite: Sprite {
-                    custom_size: Some(Vec2::new(16.0, 16.0)),
-                    ..default()
-                },
-                transform: Transform {
-                    translation: Vec3::new(-(window.width() - 16.0) / 2.0, -(window.height() - 16.0) / 2.0, 0.0),
-                    scale: Vec3::new(window.width(), 4.0, 0.0),
-                    ..default()
-                },
-                ..default()
-            }, Terrain)
-        ).insert((Name::new("Terrain"), CleanupGameStateExit));
-}
\ No newline at end of file
+pub struct Terrain {
+    pub one_way: bool
+}
+
+#[derive(Clone, Copy, Eq, PartialEq, Debug, Default, Serialize, Deserialize)]
+pub enum ArenaChoice {
+    #[default]
+    Classic,
+    Platforms
+}
+
+impl ArenaChoice {
+    pub fn label(&self) -> &'static str {
+        match self {
+            ArenaChoice::Classic => "Classic",
+            ArenaChoice::Platforms => "Platforms"
+        }
+    }
+
+    pub fn next(&self) -> Self {
+        match self {
+            ArenaChoice::Classic => ArenaChoice::Platforms,
+            ArenaChoice::Platforms => ArenaChoice::Classic
+        }
+    }
+}
+
+#[derive(AssetCollection, Resource)]
+pub struct ArenaAssets {
+    #[asset(path = "arenas/classic.arena.ron")]
+    classic: Handle<Arena>,
+
+    #[asset(path = "arenas/platforms.arena.ron")]
+    platforms: Handle<Arena>
+}
+
+// Layout of the terrain, positions are in pixels with x from the middle and y up from the bottom of the play field
+#[derive(Asset, TypePath, Clone, Deserialize)]
+pub struct Arena {
+    pub name: String,
+    // Where the player starts, and comes back after falling through a gap
+    pub player_spawn: (f32, f32),
+    pub platforms: Vec<Platform>
+}
+
+#[derive(Clone, Deserialize)]
+pub struct Platform {
+    // Middle of the platform
+    pub x: f32,
+    // Height of the top of the platform
+    pub top: f32,
+    // Size in tiles
+    pub width: u32,
+    #[serde(default = "default_platform_height")]
+    pub height: u32,
+    #[serde(default)]
+    pub one_way: bool
+}
+
+fn default_platform_height() -> u32 {
+    1
+}
+
+// Used when an arena can not be loaded, matches `classic.arena.ron`
+impl Default for Arena {
+    fn default() -> Self {
+        Self {
+            name: "Classic".to_string(),
+            player_spawn: (0.0, 350.0),
+            platforms: vec![Platform { x: 0.0, top: 40.0, width: 30, height: 1, one_way: false }]
+        }
+    }
+}
+
+// A platform of the current run in world coordinates
+pub struct PlatformBounds {
+    pub rect: Rect,
+    pub one_way: bool
+}
+
+// The arena of the current run, picked when it starts like the difficulty
+#[derive(Resource, Default)]
+pub struct ActiveArena {
+    pub platforms: Vec<PlatformBounds>,
+    pub player_spawn: Vec3
+}
+
+impl ActiveArena {
+    fn new(arena: &Arena, play_field: &PlayField) -> Self {
+        let bottom = -play_field.height / 2.0;
+
+        let platforms = arena.platforms.iter().map(|platform| {
+            let size = Vec2::new(platform.width as f32, platform.height as f32) * TILE_SIZE;
+            let center = Vec2::new(platform.x, bottom + platform.top - size.y / 2.0);
+
+            PlatformBounds { rect: Rect::from_center_size(center, size), one_way: platform.one_way }
+        }).collect();
+
+        Self { platforms, player_spawn: Vec3::new(arena.player_spawn.0, bottom + arena.player_spawn.1, 0.0) }
+    }
+
+    // Top of the highest platform at `x` that is not above `y`, things dropped there land on it
+    pub fn surface_below(&self, x: f32, y: f32) -> Option<f32> {
+        self.platforms.iter()
+            .filter(|platform| x >= platform.rect.min.x && x <= platform.rect.max.x && platform.rect.max.y <= y)
+            .map(|platform| platform.rect.max.y)
+            .max_by(f32::total_cmp)
+    }
+}
+
+#[derive(Default)]
+struct ArenaLoader;
+
+#[derive(Debug)]
+enum ArenaError {
+    Io(io::Error),
+    Ron(ron::error::SpannedError)
+}
+
+impl fmt::Display for ArenaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArenaError::Io(error) => write!(f, "could not read arena: {error}"),
+            ArenaError::Ron(error) => write!(f, "arena is invalid: {error}")
+        }
+    }
+}
+
+impl std::error::Error for ArenaError {}
+
+impl AssetLoader for ArenaLoader {
+    type Asset = Arena;
+    type Settings = ();
+    type Error = ArenaError;
+
+    async fn load<'a>(&'a self, reader: &'a mut Reader<'_>, _settings: &'a (), _load_context: &'a mut LoadContext<'_>) -> Result<Arena, ArenaError> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.map_err(ArenaError::Io)?;
+        ron::de::from_bytes(&bytes).map_err(ArenaError::Ron)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["arena.ron"]
+    }
+}
+
+pub fn apply_arena(settings: Res<RunSettings>, arena_assets: Res<ArenaAssets>, arenas: Res<Assets<Arena>>, play_field: Res<PlayField>,
+    mut active_arena: ResMut<ActiveArena>) {
+
+    let handle = match settings.arena {
+        ArenaChoice::Classic => &arena_assets.classic,
+        ArenaChoice::Platforms => &arena_assets.platforms
+    };
+
+    let arena = arenas.get(handle).cloned().unwrap_or_else(|| {
+        warn!("Arena {} is not loaded, using the classic arena", settings.arena.label());
+        Arena::default()
+    });
+
+    info!("Playing on arena \"{}\"", arena.name);
+    *active_arena = ActiveArena::new(&arena, &play_field);
+}
+
+fn spawn_terrain(mut commands: Commands, asset_server: Res<AssetServer>, active_arena: Res<ActiveArena>) {
+    let texture: Handle<Image> = asset_server.load(TERRAIN_SPRITE_PATH);
+
+    for platform in &active_arena.platforms {
+        let size = platform.rect.size();
+        let color = if platform.one_way { ONE_WAY_TINT } else { Color::WHITE };
+
+        commands.spawn((
+            SpatialBundle::from_transform(Transform::from_translation(platform.rect.center().extend(0.0))),
+            Terrain { one_way: platform.one_way },
+            Collider::new(size / 2.0, CollisionLayers::new(CollisionLayer::TERRAIN, CollisionLayer::PLAYER | CollisionLayer::KNIFE | CollisionLayer::COIN))
+        )).with_children(|commands| {
+            let tiles = (size / TILE_SIZE).round();
+
+            for column in 0..tiles.x as u32 {
+                for row in 0..tiles.y as u32 {
+                    let offset = Vec2::new(column as f32 + 0.5, row as f32 + 0.5) * TILE_SIZE - size / 2.0;
+
+                    commands.spawn(SpriteBundle {
+                        texture: texture.clone(),
+                        sprite: Sprite {
+                            color,
+                            custom_size: Some(Vec2::splat(TILE_SIZE)),
+                            ..default()
+                        },
+                        transform: Transform::from_translation(offset.extend(0.0)),
+                        ..default()
+                    });
+                }
+            }
+        }).insert((Name::new("Terrain"), CleanupGameStateExit));
+    }
+}
+
+// Everything that moves except the player, who respawns instead
+type FallingObjects = (With<Movement>, Without<Player>);
+
+// Knives and coins falling through a gap never touch the terrain, so they are removed once out of sight
+pub fn despawn_fallen(mut commands: Commands, play_field: Res<PlayField>, query: Query<(Entity, &Transform), FallingObjects>) {
+    let limit = -play_field.height / 2.0 - FALL_OUT_MARGIN;
+
+    for (entity, transform) in &query {
+        if transform.translation.y < limit {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn platform(x: f32, top: f32, width: u32, one_way: bool) -> Platform {
+        Platform { x, top, width, height: 1, one_way }
+    }
+
+    // Same layout as `platforms.arena.ron`, with one more platform high above the spawn
+    fn arena() -> ActiveArena {
+        let arena = Arena {
+            name: "Test".to_string(),
+            player_spawn: (0.0, 420.0),
+            platforms: vec![
+                platform(-420.0, 40.0, 9, false),
+                platform(0.0, 40.0, 8, false),
+                platform(420.0, 40.0, 9, false),
+                platform(-300.0, 200.0, 4, true),
+                platform(300.0, 200.0, 4, true),
+                platform(0.0, 330.0, 3, false),
+                platform(0.0, 600.0, 2, true)
+            ]
+        };
+
+        ActiveArena::new(&arena, &PlayField { width: 1200.0, height: 700.0 })
+    }
+
+    #[test]
+    fn nothing_below_a_gap() {
+        let arena = arena();
+
+        assert_eq!(arena.surface_below(-200.0, arena.player_spawn.y), None);
+        assert_eq!(arena.surface_below(200.0, arena.player_spawn.y), None);
+        assert_eq!(arena.surface_below(-160.0, arena.player_spawn.y), Some(-310.0));
+    }
+
+    #[test]
+    fn highest_stacked_platform_below_wins() {
+        let arena = arena();
+
+        assert_eq!(arena.surface_below(-300.0, arena.player_spawn.y), Some(-150.0));
+        assert_eq!(arena.surface_below(-300.0, -200.0), Some(-310.0));
+        assert_eq!(arena.surface_below(0.0, arena.player_spawn.y), Some(-20.0));
+        assert_eq!(arena.surface_below(100.0, arena.player_spawn.y), Some(-310.0));
+    }
+
+    #[test]
+    fn platforms_above_are_skipped() {
+        let arena = arena();
+
+        assert_eq!(arena.player_spawn, Vec3::new(0.0, 70.0, 0.0));
+        assert_eq!(arena.surface_below(0.0, arena.player_spawn.y), Some(-20.0));
+        assert_eq!(arena.surface_below(0.0, 300.0), Some(250.0));
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+
+use crate::controls::controls::{key_name, Action, ActionMap, Rebind, BINDING_SLOTS};
+use crate::ui::ui::InteractionChanged;
+use crate::{CleanupControlsStateExit, GameState};
+
+pub struct ControlsMenuPlugin<GameState: States> {
+    pub state: GameState
+}
+
+impl Plugin for ControlsMenuPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Controls), setup_controls_menu);
+        app.add_systems(Update, ((controls_buttons_pressed_system, listen_for_rebind_key, update_binding_labels, update_rebind_message).chain(), controls_buttons_hovered_system)
+            .run_if(in_state(self.state.clone())));
+    }
+}
+
+#[derive(Component)]
+pub enum ControlsButton {
+    Binding { action: Action, slot: usize },
+    AxisPolicy,
+    ClearKey,
+    CancelRebind,
+    ResetDefaults,
+    Back
+}
+
+#[derive(Component)]
+struct BindingLabel {
+    action: Action,
+    slot: usize
+}
+
+#[derive(Component)]
+struct RebindMessage;
+
+#[derive(Component)]
+struct AxisPolicyLabel;
+
+#[derive(Resource, Default)]
+struct RebindState {
+    waiting_for: Option<(Action, usize)>,
+    message: String
+}
+
+fn setup_controls_menu(mut commands: Commands) {
+
+    commands.insert_resource(RebindState {
+        waiting_for: None,
+        message: "Click a key to change it".to_string()
+    });
+
+    commands.spawn( NodeBundle {
+        style: Style {
+            height: Val::Percent(100.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+            // Title
+            parent.spawn( TextBundle {
+                style: Style {
+                    margin: UiRect::bottom(Val::Px(20.0)),
+                    ..default()
+                },
+                text: Text::from_section("Controls",
+                        TextStyle {
+                            font_size: 60.0,
+                            color: Color::WHITE,
+                            ..default()
+                        }),
+                ..default()
+            });
+
+            // One row per action with a button for every key slot
+            for action in Action::ALL {
+                parent.spawn( NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        style: Style {
+                            width: Val::Px(220.0),
+                            ..default()
+                        },
+                        text: Text::from_section(action.label(),
+                                TextStyle {
+                                    font_size: 32.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                        ..default()
+                    });
+
+                    for slot in 0..BINDING_SLOTS {
+                        parent.spawn( ButtonBundle {
+                            style: Style {
+                                width: Val::Px(200.0),
+                                height: Val::Px(50.0),
+                                margin: UiRect::all(Val::Px(5.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..Default::default()
+                            },
+                            background_color: Color::srgba(1.0, 1.0, 1.0, 0.08).into(),
+                            ..default()
+                        }).with_children(|parent| {
+                            parent.spawn((TextBundle {
+                                text: Text::from_section("",
+                                        TextStyle {
+                                            font_size: 28.0,
+                                            color: Color::WHITE,
+                                            ..default()
+                                        }),
+                                        ..default()
+                            }, BindingLabel { action, slot }));
+                        }).insert(ControlsButton::Binding { action, slot });
+                    }
+                });
+            }
+
+            // Policy for holding left and right at the same time
+            parent.spawn( ButtonBundle {
+                style: Style {
+                    width: Val::Px(640.0),
+                    height: Val::Px(50.0),
+                    margin: UiRect::top(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: Color::srgba(1.0, 1.0, 1.0, 0.08).into(),
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn((TextBundle {
+                    text: Text::from_section("",
+                            TextStyle {
+                                font_size: 28.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                            ..default()
+                }, AxisPolicyLabel));
+            }).insert(ControlsButton::AxisPolicy);
+
+            // Status line, shows what is being rebound and any conflict
+            parent.spawn((TextBundle {
+                style: Style {
+                    margin: UiRect::vertical(Val::Px(15.0)),
+                    ..default()
+                },
+                text: Text::from_section("",
+                        TextStyle {
+                            font_size: 24.0,
+                            color: Color::srgb(1.0, 0.85, 0.2),
+                            ..default()
+                        }),
+                ..default()
+            }, RebindMessage));
+
+            // Every key can be bound, so clearing and cancelling a rebind are buttons instead of keys
+            parent.spawn( NodeBundle {
+                style: Style {
+                    align_items: AlignItems::Center,
+                    margin: UiRect::bottom(Val::Px(10.0)),
+                    ..default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                for (label, button) in [("Clear Key", ControlsButton::ClearKey), ("Cancel", ControlsButton::CancelRebind)] {
+                    parent.spawn( ButtonBundle {
+                        style: Style {
+                            width: Val::Px(200.0),
+                            height: Val::Px(50.0),
+                            margin: UiRect::all(Val::Px(5.0)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        background_color: Color::srgba(1.0, 1.0, 1.0, 0.08).into(),
+                        ..default()
+                    }).with_children(|parent| {
+                        parent.spawn( TextBundle {
+                            text: Text::from_section(label,
+                                    TextStyle {
+                                        font_size: 28.0,
+                                        color: Color::WHITE,
+                                        ..default()
+                                    }),
+                                    ..default()
+                        });
+                    }).insert(button);
+                }
+            });
+
+            for (label, button) in [("Reset to Defaults", ControlsButton::ResetDefaults), ("Back", ControlsButton::Back)] {
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(350.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section(label,
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }).insert(button);
+            }
+
+        }).insert(CleanupControlsStateExit);
+}
+
+fn controls_buttons_pressed_system(
+    interaction_query: Query<(&Interaction, &ControlsButton), InteractionChanged<Button>>,
+    mut rebind_state: ResMut<RebindState>,
+    mut action_map: ResMut<ActionMap>,
+    mut app_state: ResMut<NextState<GameState>>) {
+
+    for (interaction, controls_button) in interaction_query.iter() {
+        if let Interaction::Pressed = *interaction {
+            match controls_button {
+                ControlsButton::Binding { action, slot } => {
+                    rebind_state.waiting_for = Some((*action, *slot));
+                    rebind_state.message = format!("Press a key for {}", action.label());
+                },
+                ControlsButton::ClearKey => {
+                    let Some((action, slot)) = rebind_state.waiting_for.take() else {
+                        rebind_state.message = "Click a key first".to_string();
+                        continue;
+                    };
+
+                    rebind_state.message = if action_map.clear(action, slot) {
+                        action_map.save();
+                        format!("Cleared a key from {}", action.label())
+                    } else {
+                        format!("{} needs at least one key", action.label())
+                    };
+                },
+                ControlsButton::CancelRebind => {
+                    if rebind_state.waiting_for.take().is_some() {
+                        rebind_state.message = "Cancelled".to_string();
+                    }
+                },
+                ControlsButton::AxisPolicy => {
+                    action_map.axis_policy = action_map.axis_policy.next();
+                    action_map.save();
+                },
+                ControlsButton::ResetDefaults => {
+                    *action_map = ActionMap::default();
+                    action_map.save();
+                    rebind_state.waiting_for = None;
+                    rebind_state.message = "Controls reset to defaults".to_string();
+                },
+                ControlsButton::Back => {
+                    app_state.set(GameState::Menu);
+                },
+            }
+        }
+    }
+}
+
+fn listen_for_rebind_key(input: Res<ButtonInput<KeyCode>>, mut rebind_state: ResMut<RebindState>, mut action_map: ResMut<ActionMap>) {
+    let Some((action, slot)) = rebind_state.waiting_for else {
+        return;
+    };
+
+    let Some(key) = input.get_just_pressed().next().copied() else {
+        return;
+    };
+
+    rebind_state.waiting_for = None;
+
+    let rebind = action_map.bind(action, slot, key);
+
+    rebind_state.message = match rebind {
+        Rebind::Bound => format!("{} is now {}", key_name(key), action.label()),
+        Rebind::Taken(conflict) => format!("{} is now {}, it was removed from {}", key_name(key), action.label(), conflict.label()),
+        Rebind::Swapped(conflict, swapped) => format!("{} is now {}, {} is now {}", key_name(key), action.label(), key_name(swapped), conflict.label()),
+        Rebind::Refused(conflict) => format!("{} is the last key of {}", key_name(key), conflict.label()),
+    };
+
+    if !matches!(rebind, Rebind::Refused(_)) {
+        action_map.save();
+    }
+}
+
+fn update_binding_labels(action_map: Res<ActionMap>, rebind_state: Res<RebindState>, mut label_query: Query<(&BindingLabel, &mut Text)>,
+    mut policy_query: Query<&mut Text, (With<AxisPolicyLabel>, Without<BindingLabel>)>) {
+    if !action_map.is_changed() && !rebind_state.is_changed() {
+        return;
+    }
+
+    for mut text in &mut policy_query {
+        text.sections[0].value = format!("Left + Right: {}", action_map.axis_policy.label());
+    }
+
+    for (label, mut text) in &mut label_query {
+        text.sections[0].value = if rebind_state.waiting_for == Some((label.action, label.slot)) {
+            "...".to_string()
+        } else {
+            action_map.keys(label.action)[label.slot].map_or("-".to_string(), key_name)
+        };
+    }
+}
+
+fn update_rebind_message(rebind_state: Res<RebindState>, mut message_query: Query<&mut Text, With<RebindMessage>>) {
+    if !rebind_state.is_changed() {
+        return;
+    }
+
+    for mut text in &mut message_query {
+        text.sections[0].value = rebind_state.message.clone();
+    }
+}
+
+fn controls_buttons_hovered_system(interaction_query: Query<(&Interaction, &Children, &ControlsButton), Changed<Interaction>>, mut text_query: Query<&mut Text>) {
+
+    for (interaction, children, controls_button) in interaction_query.iter() {
+        let base_size = match controls_button {
+            ControlsButton::Binding { .. } | ControlsButton::AxisPolicy | ControlsButton::ClearKey | ControlsButton::CancelRebind => 28.0,
+            _ => 40.0
+        };
+
+        let font_size = match interaction {
+            Interaction::Pressed => base_size + 5.0,
+            Interaction::Hovered => base_size + 10.0,
+            Interaction::None => base_size,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                for section in &mut text.sections {
+                    section.style.font_size = font_size;
+                }
+            }
+        }
+    }
+}
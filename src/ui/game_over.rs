@@ -0,0 +1,197 @@
+use bevy::prelude::*;
+
+use crate::highscores::highscores::{HighScoreNameInput, HighScores};
+use crate::random::random::GameRng;
+use crate::replay::replay::ReplayPlayback;
+use crate::ui::ui::InteractionChanged;
+use crate::{points::points::Points, stats::stats::RunStats, CleanupGameOverStateExit, GameState, Level};
+
+use super::text_input::TextInput;
+
+const NAME_MAX_LENGTH: usize = 12;
+
+pub struct GameOverPlugin<GameState: States> {
+    pub state: GameState
+}
+
+impl Plugin for GameOverPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::GameOver), setup_game_over);
+        app.add_systems(Update, (game_over_buttons_pressed_system, game_over_buttons_hovered_system).run_if(in_state(self.state.clone())));
+    }
+}
+
+#[derive(Component)]
+pub enum GameOverButton {
+    Retry,
+    MainMenu
+}
+
+fn setup_game_over(mut commands: Commands, points: Res<Points>, level: Res<Level>, run_stats: Res<RunStats>, high_scores: Res<HighScores>, game_rng: Res<GameRng>,
+    replay_playback: Option<Res<ReplayPlayback>>) {
+
+    let summary = [
+        format!("Points: {}", points.value),
+        format!("Level reached: {}", level.value),
+        format!("Coins collected: {}", run_stats.coins_collected),
+        format!("Knives dodged: {}", run_stats.knives_dodged),
+        format!("Near misses: {}", run_stats.near_misses),
+        format!("Run time: {:.1}s", run_stats.run_time),
+        format!("Seed: {}", game_rng.seed),
+    ];
+
+    commands.spawn( NodeBundle {
+        style: Style {
+            height: Val::Percent(100.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+            // Title
+            parent.spawn( TextBundle {
+                style: Style {
+                    margin: UiRect::bottom(Val::Px(20.0)),
+                    ..default()
+                },
+                text: Text::from_section("Game Over",
+                        TextStyle {
+                            font_size: 60.0,
+                            color: Color::WHITE,
+                            ..default()
+                        }),
+                ..default()
+            });
+
+            // Run summary
+            for line in summary {
+                parent.spawn( TextBundle {
+                    style: Style {
+                        margin: UiRect::bottom(Val::Px(5.0)),
+                        ..default()
+                    },
+                    text: Text::from_section(line,
+                            TextStyle {
+                                font_size: 32.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                    ..default()
+                });
+            }
+
+            // High score name entry, replays never count as new scores
+            if high_scores.qualifies(points.value) && replay_playback.is_none() {
+                parent.spawn( TextBundle {
+                    style: Style {
+                        margin: UiRect::top(Val::Px(15.0)),
+                        ..default()
+                    },
+                    text: Text::from_section("New high score! Type your name and press Enter",
+                            TextStyle {
+                                font_size: 28.0,
+                                color: Color::srgb(1.0, 0.85, 0.2),
+                                ..default()
+                            }),
+                    ..default()
+                });
+
+                parent.spawn((TextBundle {
+                    text: Text::from_section("_",
+                            TextStyle {
+                                font_size: 36.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                    ..default()
+                }, TextInput::new(NAME_MAX_LENGTH), HighScoreNameInput));
+            }
+
+            // Retry button
+            parent.spawn( ButtonBundle {
+                style: Style {
+                    width: Val::Px(250.0),
+                    height: Val::Px(65.0),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn( TextBundle {
+                    text: Text::from_section("Retry",
+                            TextStyle {
+                                font_size: 40.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                            ..default()
+                });
+            }).insert(GameOverButton::Retry);
+
+            // Main menu button
+            parent.spawn( ButtonBundle {
+                style: Style {
+                    width: Val::Px(250.0),
+                    height: Val::Px(65.0),
+                    margin: UiRect::all(Val::Px(10.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn( TextBundle {
+                    text: Text::from_section("Main Menu",
+                            TextStyle {
+                                font_size: 40.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                            ..default()
+                });
+            }).insert(GameOverButton::MainMenu);
+
+        }).insert(CleanupGameOverStateExit);
+}
+
+fn game_over_buttons_pressed_system(
+    interaction_query: Query<(&Interaction, &GameOverButton), InteractionChanged<Button>>,
+    mut app_state: ResMut<NextState<GameState>>) {
+
+    for (interaction, game_over_button) in interaction_query.iter() {
+        if let Interaction::Pressed = *interaction {
+            match game_over_button {
+                GameOverButton::Retry => {
+                    app_state.set(GameState::Game);
+                },
+                GameOverButton::MainMenu => {
+                    app_state.set(GameState::Menu);
+                },
+            }
+        }
+    }
+}
+
+fn game_over_buttons_hovered_system(interaction_query: Query<(&Interaction, &Children), InteractionChanged<GameOverButton>>, mut text_query: Query<&mut Text>) {
+
+    for (interaction, children) in interaction_query.iter() {
+        let font_size = match interaction {
+            Interaction::Pressed => 45.0,
+            Interaction::Hovered => 50.0,
+            Interaction::None => 40.0,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                for section in &mut text.sections {
+                    section.style.font_size = font_size;
+                }
+            }
+        }
+    }
+}
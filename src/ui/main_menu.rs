@@ -1,6 +1,16 @@
+use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
 
-use crate::{CleanupMenuStateExit, GameState};
+use crate::{highscores::highscores::HighScores, random::random::SeedSelection, replay::replay::ReplayPlayback, settings::settings::GameSettings, CleanupMenuStateExit, GameState};
+use crate::ui::ui::InteractionChanged;
+
+use super::text_input::{type_into_text_input, TextInput};
+
+const SEED_MAX_LENGTH: usize = 20;
+const GAMEPAD_STICK_THRESHOLD: f32 = 0.5;
+const FOCUSED_BUTTON_COLOR: Color = Color::srgba(1.0, 1.0, 1.0, 0.15);
+// Options a loaded replay decides are greyed out
+const LOCKED_TEXT_COLOR: Color = Color::srgb(0.6, 0.6, 0.6);
 
 pub struct MainMenuPlugin<GameState: States> {
     pub state: GameState
@@ -9,17 +19,48 @@ pub struct MainMenuPlugin<GameState: States> {
 impl Plugin for MainMenuPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Menu), setup_menu);
-        app.add_systems(Update, (main_menu_buttons_pressed_system, main_menu_buttons_hovered_system).run_if(in_state(self.state.clone())));
+        app.add_systems(Update, (main_menu_buttons_hovered_system, update_seed_selection.after(type_into_text_input), (gamepad_menu_navigation, (main_menu_buttons_pressed_system, highlight_focused_button)).chain(), update_difficulty_label, update_arena_label).run_if(in_state(self.state.clone())));
     }
 }
 
 #[derive(Component)]
+pub struct SeedInput;
+
+#[derive(Component)]
+struct DifficultyLabel;
+
+#[derive(Component)]
+struct ArenaLabel;
+
+// Buttons are listed in the order they appear on screen, gamepad navigation follows it
+#[derive(Component, Clone, Copy)]
 pub enum MenuButton {
     Play,
+    Difficulty,
+    Arena,
+    Controls,
+    Settings,
     Quit
 }
 
-fn setup_menu(mut commands: Commands) {
+#[derive(Resource, Default)]
+struct MenuFocus {
+    index: usize,
+    stick_held: bool
+}
+
+fn setup_menu(mut commands: Commands, high_scores: Res<HighScores>, seed_selection: Res<SeedSelection>, playback: Option<Res<ReplayPlayback>>) {
+
+    commands.insert_resource(MenuFocus::default());
+
+    // Twenty digits go past the largest seed, so the value has to parse too
+    let mut seed_input = TextInput { valid: |value| value.parse::<u64>().is_ok(), ..TextInput::numeric(SEED_MAX_LENGTH) };
+    if let Some(seed) = seed_selection.seed {
+        seed_input.value = seed.to_string();
+    }
+
+    // A loaded replay decides the seed, it is shown but can not be typed over
+    let replay_seed = playback.map(|playback| playback.seed());
 
     commands.spawn( NodeBundle {
         style: Style {
@@ -27,77 +68,354 @@ fn setup_menu(mut commands: Commands) {
             width: Val::Percent(100.0),
             justify_content: JustifyContent::Center,
             align_items: AlignItems::Center,
-            flex_direction: FlexDirection::Column,
+            flex_direction: FlexDirection::Row,
             ..default()
         },
         ..default()
     }).with_children(|parent| {
-            // Play Button
-            parent.spawn( ButtonBundle {
+            // Buttons
+            parent.spawn( NodeBundle {
                 style: Style {
-                    width: Val::Px(200.0),
-                    height: Val::Px(65.0),
-                    margin: UiRect::all(Val::Px(10.0)),
-                    justify_content: JustifyContent::Center,
                     align_items: AlignItems::Center,
-                    ..Default::default()
+                    flex_direction: FlexDirection::Column,
+                    ..default()
                 },
                 ..default()
             }).with_children(|parent| {
-                parent.spawn( TextBundle {
-                    text: Text::from_section("Play",
-                            TextStyle {
-                                font_size: 40.0,
-                                color: Color::WHITE,
+                // Play Button
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section("Play",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
                                 ..default()
-                            }),
+                    });
+                }).insert(MenuButton::Play);
+
+                // Difficulty button, cycles through the profiles
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(320.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn((TextBundle {
+                        text: Text::from_section("",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    }, DifficultyLabel));
+                }).insert(MenuButton::Difficulty);
+
+                // Arena button, cycles through the terrain layouts
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(320.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn((TextBundle {
+                        text: Text::from_section("",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    }, ArenaLabel));
+                }).insert(MenuButton::Arena);
+
+                // Controls button
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section("Controls",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }).insert(MenuButton::Controls);
+
+                // Settings button
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section("Settings",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }).insert(MenuButton::Settings);
+
+                //Quit button
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(200.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    }, 
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section("Quit",
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }).insert(MenuButton::Quit);
+
+                // Seed entry
+                parent.spawn( NodeBundle {
+                    style: Style {
+                        margin: UiRect::top(Val::Px(10.0)),
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        style: Style {
+                            margin: UiRect::right(Val::Px(10.0)),
                             ..default()
+                        },
+                        text: Text::from_section(if replay_seed.is_some() { "Replay seed:" } else { "Seed (blank for random):" },
+                                TextStyle {
+                                    font_size: 24.0,
+                                    color: Color::srgb(0.6, 0.6, 0.6),
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+
+                    let mut seed_text = parent.spawn( TextBundle {
+                        text: Text::from_section(replay_seed.map_or("_".to_string(), |seed| seed.to_string()),
+                                TextStyle {
+                                    font_size: 24.0,
+                                    color: if replay_seed.is_some() { LOCKED_TEXT_COLOR } else { Color::WHITE },
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+
+                    if replay_seed.is_none() {
+                        seed_text.insert((seed_input, SeedInput));
+                    }
                 });
-            }).insert(MenuButton::Play);
+            });
 
-            //Quit button
-            parent.spawn( ButtonBundle {
+            // High score table, next to the buttons so the menu fits the window
+            parent.spawn( NodeBundle {
                 style: Style {
-                    width: Val::Px(200.0),
-                    height: Val::Px(65.0),
-                    margin: UiRect::all(Val::Px(10.0)),
-                    justify_content: JustifyContent::Center,
+                    margin: UiRect::left(Val::Px(80.0)),
                     align_items: AlignItems::Center,
-                    ..Default::default()
-                }, 
+                    flex_direction: FlexDirection::Column,
+                    ..default()
+                },
                 ..default()
             }).with_children(|parent| {
                 parent.spawn( TextBundle {
-                    text: Text::from_section("Quit",
+                    style: Style {
+                        margin: UiRect::bottom(Val::Px(10.0)),
+                        ..default()
+                    },
+                    text: Text::from_section("High Scores",
                             TextStyle {
-                                font_size: 40.0,
+                                font_size: 32.0,
                                 color: Color::WHITE,
                                 ..default()
                             }),
                             ..default()
                 });
-            }).insert(MenuButton::Quit);
+
+                if high_scores.entries.is_empty() {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section("No scores yet",
+                                TextStyle {
+                                    font_size: 24.0,
+                                    color: Color::srgb(0.6, 0.6, 0.6),
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }
+
+                for (rank, entry) in high_scores.entries.iter().enumerate() {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section(format!("{:>2}. {:<12} {:>6}  Lv {}", rank + 1, entry.name, entry.points, entry.level),
+                                TextStyle {
+                                    font_size: 24.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }
+            });
 
         }).insert(CleanupMenuStateExit); 
 }
 
 
 fn main_menu_buttons_pressed_system(
-    mut interaction_query: Query<(&Interaction, &MenuButton), With<Button>>,
-    mut app_state: ResMut<NextState<GameState>>,
-    mut exit: EventWriter<AppExit>) {
+    mut interaction_query: Query<(&Interaction, &MenuButton), InteractionChanged<Button>>,
+    mut menu_actions: MenuActions) {
 
     for (interaction, menu_button) in interaction_query.iter_mut() {
         if let Interaction::Pressed = *interaction {
-            match menu_button {
-                MenuButton::Play => {
-                    app_state.set(GameState::Game);
-                },
-                MenuButton::Quit => {
-                    exit.send(AppExit::Success);
-                },
+            menu_actions.activate(*menu_button);
+        }
+    }
+}
+
+// Everything the menu buttons change, shared by the mouse and the gamepad
+#[derive(SystemParam)]
+struct MenuActions<'w> {
+    app_state: ResMut<'w, NextState<GameState>>,
+    settings: ResMut<'w, GameSettings>,
+    playback: Option<Res<'w, ReplayPlayback>>,
+    exit: EventWriter<'w, AppExit>
+}
+
+impl MenuActions<'_> {
+    fn activate(&mut self, menu_button: MenuButton) {
+        match menu_button {
+            MenuButton::Play => {
+                self.app_state.set(GameState::Game);
+            },
+            // A loaded replay decides the difficulty and the arena
+            MenuButton::Difficulty | MenuButton::Arena if self.playback.is_some() => {},
+            MenuButton::Difficulty => {
+                self.settings.difficulty = self.settings.difficulty.next();
+                self.settings.save();
+            },
+            MenuButton::Arena => {
+                self.settings.arena = self.settings.arena.next();
+                self.settings.save();
+            },
+            MenuButton::Controls => {
+                self.app_state.set(GameState::Controls);
+            },
+            MenuButton::Settings => {
+                self.app_state.set(GameState::Settings);
+            },
+            MenuButton::Quit => {
+                self.exit.send(AppExit::Success);
+            },
+        }
+    }
+}
+
+fn sorted_menu_buttons(button_query: &Query<&MenuButton>) -> Vec<MenuButton> {
+    let mut buttons: Vec<MenuButton> = button_query.iter().copied().collect();
+    buttons.sort_by_key(|button| *button as usize);
+    buttons
+}
+
+fn gamepad_menu_navigation(gamepads: Res<Gamepads>, gamepad_buttons: Res<ButtonInput<GamepadButton>>, gamepad_axes: Res<Axis<GamepadAxis>>,
+    button_query: Query<&MenuButton>, mut focus: ResMut<MenuFocus>, mut menu_actions: MenuActions) {
+
+    let buttons = sorted_menu_buttons(&button_query);
+    if buttons.is_empty() {
+        return;
+    }
+
+    let mut step: i32 = 0;
+    let mut stick_held = false;
+
+    for gamepad in gamepads.iter() {
+        if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadUp)) {
+            step -= 1;
+        }
+
+        if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::DPadDown)) {
+            step += 1;
+        }
+
+        // The stick only moves the focus once per push, it has to go back to the center before moving again
+        let stick_y = gamepad_axes.get(GamepadAxis::new(gamepad, GamepadAxisType::LeftStickY)).unwrap_or(0.0);
+        if stick_y.abs() > GAMEPAD_STICK_THRESHOLD {
+            if !focus.stick_held {
+                step += if stick_y > 0.0 { -1 } else { 1 };
             }
+            stick_held = true;
         }
+
+        if gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::South)) {
+            menu_actions.activate(buttons[focus.index.min(buttons.len() - 1)]);
+        }
+    }
+
+    focus.stick_held = stick_held;
+    focus.index = (focus.index as i32 + step).rem_euclid(buttons.len() as i32) as usize;
+}
+
+fn highlight_focused_button(focus: Res<MenuFocus>, mut button_query: Query<(&MenuButton, &mut BackgroundColor)>) {
+    if !focus.is_changed() {
+        return;
+    }
+
+    for (menu_button, mut background_color) in &mut button_query {
+        *background_color = if *menu_button as usize == focus.index {
+            FOCUSED_BUTTON_COLOR.into()
+        } else {
+            Color::NONE.into()
+        };
     }
 }
 
@@ -123,4 +441,41 @@ fn main_menu_buttons_hovered_system(mut interaction_query: Query<(&Interaction,
         }
     }
 
-}
\ No newline at end of file
+}
+
+fn update_seed_selection(seed_input_query: Query<&TextInput, (With<SeedInput>, Changed<TextInput>)>, mut seed_selection: ResMut<SeedSelection>) {
+    for seed_input in seed_input_query.iter() {
+        seed_selection.seed = seed_input.value.parse().ok();
+    }
+}
+
+// Labels show what the next run plays with, which is the replay's choice while one is loaded
+fn update_difficulty_label(settings: Res<GameSettings>, playback: Option<Res<ReplayPlayback>>, mut label_query: Query<&mut Text, With<DifficultyLabel>>,
+    new_labels: Query<(), Added<DifficultyLabel>>) {
+
+    if !settings.is_changed() && new_labels.is_empty() {
+        return;
+    }
+
+    let difficulty = playback.as_ref().map_or(settings.difficulty, |playback| playback.run_settings().difficulty);
+
+    for mut text in &mut label_query {
+        text.sections[0].value = format!("Difficulty: {}", difficulty.label());
+        text.sections[0].style.color = if playback.is_some() { LOCKED_TEXT_COLOR } else { Color::WHITE };
+    }
+}
+
+fn update_arena_label(settings: Res<GameSettings>, playback: Option<Res<ReplayPlayback>>, mut label_query: Query<&mut Text, With<ArenaLabel>>,
+    new_labels: Query<(), Added<ArenaLabel>>) {
+
+    if !settings.is_changed() && new_labels.is_empty() {
+        return;
+    }
+
+    let arena = playback.as_ref().map_or(settings.arena, |playback| playback.run_settings().arena);
+
+    for mut text in &mut label_query {
+        text.sections[0].value = format!("Arena: {}", arena.label());
+        text.sections[0].style.color = if playback.is_some() { LOCKED_TEXT_COLOR } else { Color::WHITE };
+    }
+}
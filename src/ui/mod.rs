@@ -1,2 +1,7 @@
 pub mod ui;
 pub mod main_menu;
+pub mod game_over;
+pub mod pause_menu;
+pub mod text_input;
+pub mod controls_menu;
+pub mod settings_menu;
\ No newline at end of file
@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+
+use crate::controls::controls::{Action, ActionMap};
+use crate::ui::ui::InteractionChanged;
+use crate::{GameState, PauseState};
+
+pub struct PauseMenuPlugin<PauseState: States> {
+    pub state: PauseState
+}
+
+impl Plugin for PauseMenuPlugin<PauseState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(PauseState::Paused), setup_pause_menu);
+        app.add_systems(OnExit(PauseState::Paused), cleanup_pause_menu);
+        app.add_systems(Update, toggle_pause.run_if(in_state(GameState::Game)));
+        app.add_systems(Update, (pause_buttons_pressed_system.after(toggle_pause), pause_buttons_hovered_system).run_if(in_state(self.state.clone())));
+    }
+}
+
+#[derive(Component)]
+pub struct PauseMenu;
+
+#[derive(Component)]
+pub enum PauseButton {
+    Resume,
+    Restart,
+    QuitToMenu
+}
+
+fn toggle_pause(input: Res<ButtonInput<KeyCode>>, action_map: Res<ActionMap>, pause_state: Res<State<PauseState>>, mut next_pause_state: ResMut<NextState<PauseState>>) {
+    if action_map.just_pressed(Action::Pause, &input) {
+        match pause_state.get() {
+            PauseState::Running => next_pause_state.set(PauseState::Paused),
+            PauseState::Paused => next_pause_state.set(PauseState::Running),
+        }
+    }
+}
+
+fn setup_pause_menu(mut commands: Commands) {
+
+    commands.spawn( NodeBundle {
+        style: Style {
+            height: Val::Percent(100.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        background_color: Color::srgba(0.0, 0.0, 0.0, 0.6).into(),
+        z_index: ZIndex::Global(10),
+        ..default()
+    }).with_children(|parent| {
+            // Title
+            parent.spawn( TextBundle {
+                style: Style {
+                    margin: UiRect::bottom(Val::Px(20.0)),
+                    ..default()
+                },
+                text: Text::from_section("Paused",
+                        TextStyle {
+                            font_size: 60.0,
+                            color: Color::WHITE,
+                            ..default()
+                        }),
+                ..default()
+            });
+
+            for (label, button) in [("Resume", PauseButton::Resume), ("Restart", PauseButton::Restart), ("Quit to Menu", PauseButton::QuitToMenu)] {
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(300.0),
+                        height: Val::Px(65.0),
+                        margin: UiRect::all(Val::Px(10.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn( TextBundle {
+                        text: Text::from_section(label,
+                                TextStyle {
+                                    font_size: 40.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    });
+                }).insert(button);
+            }
+
+        }).insert(PauseMenu);
+}
+
+fn cleanup_pause_menu(mut commands: Commands, query: Query<Entity, With<PauseMenu>>) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+fn pause_buttons_pressed_system(
+    interaction_query: Query<(&Interaction, &PauseButton), InteractionChanged<Button>>,
+    mut pause_state: ResMut<NextState<PauseState>>,
+    mut app_state: ResMut<NextState<GameState>>) {
+
+    for (interaction, pause_button) in interaction_query.iter() {
+        if let Interaction::Pressed = *interaction {
+            match pause_button {
+                PauseButton::Resume => {
+                    pause_state.set(PauseState::Running);
+                },
+                PauseButton::Restart => {
+                    app_state.set(GameState::Restarting);
+                },
+                PauseButton::QuitToMenu => {
+                    app_state.set(GameState::Menu);
+                },
+            }
+        }
+    }
+}
+
+fn pause_buttons_hovered_system(interaction_query: Query<(&Interaction, &Children), InteractionChanged<PauseButton>>, mut text_query: Query<&mut Text>) {
+
+    for (interaction, children) in interaction_query.iter() {
+        let font_size = match interaction {
+            Interaction::Pressed => 45.0,
+            Interaction::Hovered => 50.0,
+            Interaction::None => 40.0,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                for section in &mut text.sections {
+                    section.style.font_size = font_size;
+                }
+            }
+        }
+    }
+}
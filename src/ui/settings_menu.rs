@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+
+use crate::settings::settings::GameSettings;
+use crate::ui::ui::InteractionChanged;
+use crate::{CleanupSettingsStateExit, GameState};
+
+pub struct SettingsMenuPlugin<GameState: States> {
+    pub state: GameState
+}
+
+impl Plugin for SettingsMenuPlugin<GameState> {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Settings), setup_settings_menu);
+        app.add_systems(Update, (settings_buttons_pressed_system, settings_buttons_hovered_system, update_setting_labels)
+            .run_if(in_state(self.state.clone())));
+    }
+}
+
+// Every on/off option shown on the settings screen
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum Setting {
+    ExpertMode,
+    StuckKnives
+}
+
+impl Setting {
+    const ALL: [Setting; 2] = [Setting::ExpertMode, Setting::StuckKnives];
+
+    fn label(&self) -> &'static str {
+        match self {
+            Setting::ExpertMode => "Expert mode (no knife warnings)",
+            Setting::StuckKnives => "Knives stick in the ground",
+        }
+    }
+
+    fn is_on(&self, settings: &GameSettings) -> bool {
+        match self {
+            Setting::ExpertMode => settings.expert_mode,
+            Setting::StuckKnives => settings.stuck_knives,
+        }
+    }
+
+    fn toggle(&self, settings: &mut GameSettings) {
+        match self {
+            Setting::ExpertMode => settings.expert_mode = !settings.expert_mode,
+            Setting::StuckKnives => settings.stuck_knives = !settings.stuck_knives,
+        }
+    }
+}
+
+#[derive(Component)]
+pub enum SettingsButton {
+    Toggle(Setting),
+    Back
+}
+
+#[derive(Component)]
+struct SettingLabel(Setting);
+
+fn setup_settings_menu(mut commands: Commands) {
+
+    commands.spawn( NodeBundle {
+        style: Style {
+            height: Val::Percent(100.0),
+            width: Val::Percent(100.0),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    }).with_children(|parent| {
+            // Title
+            parent.spawn( TextBundle {
+                style: Style {
+                    margin: UiRect::bottom(Val::Px(20.0)),
+                    ..default()
+                },
+                text: Text::from_section("Settings",
+                        TextStyle {
+                            font_size: 60.0,
+                            color: Color::WHITE,
+                            ..default()
+                        }),
+                ..default()
+            });
+
+            for setting in Setting::ALL {
+                parent.spawn( ButtonBundle {
+                    style: Style {
+                        width: Val::Px(640.0),
+                        height: Val::Px(50.0),
+                        margin: UiRect::all(Val::Px(5.0)),
+                        justify_content: JustifyContent::Center,
+                        align_items: AlignItems::Center,
+                        ..Default::default()
+                    },
+                    background_color: Color::srgba(1.0, 1.0, 1.0, 0.08).into(),
+                    ..default()
+                }).with_children(|parent| {
+                    parent.spawn((TextBundle {
+                        text: Text::from_section("",
+                                TextStyle {
+                                    font_size: 28.0,
+                                    color: Color::WHITE,
+                                    ..default()
+                                }),
+                                ..default()
+                    }, SettingLabel(setting)));
+                }).insert(SettingsButton::Toggle(setting));
+            }
+
+            parent.spawn( ButtonBundle {
+                style: Style {
+                    width: Val::Px(350.0),
+                    height: Val::Px(65.0),
+                    margin: UiRect::top(Val::Px(20.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                ..default()
+            }).with_children(|parent| {
+                parent.spawn( TextBundle {
+                    text: Text::from_section("Back",
+                            TextStyle {
+                                font_size: 40.0,
+                                color: Color::WHITE,
+                                ..default()
+                            }),
+                            ..default()
+                });
+            }).insert(SettingsButton::Back);
+
+        }).insert(CleanupSettingsStateExit);
+}
+
+fn settings_buttons_pressed_system(
+    interaction_query: Query<(&Interaction, &SettingsButton), InteractionChanged<Button>>,
+    mut settings: ResMut<GameSettings>,
+    mut app_state: ResMut<NextState<GameState>>) {
+
+    for (interaction, settings_button) in interaction_query.iter() {
+        if let Interaction::Pressed = *interaction {
+            match settings_button {
+                SettingsButton::Toggle(setting) => {
+                    setting.toggle(&mut settings);
+                    settings.save();
+                },
+                SettingsButton::Back => {
+                    app_state.set(GameState::Menu);
+                },
+            }
+        }
+    }
+}
+
+fn update_setting_labels(settings: Res<GameSettings>, mut label_query: Query<(&SettingLabel, &mut Text)>, new_labels: Query<(), Added<SettingLabel>>) {
+    if !settings.is_changed() && new_labels.is_empty() {
+        return;
+    }
+
+    for (label, mut text) in &mut label_query {
+        let state = if label.0.is_on(&settings) { "On" } else { "Off" };
+        text.sections[0].value = format!("{}: {}", label.0.label(), state);
+    }
+}
+
+fn settings_buttons_hovered_system(interaction_query: Query<(&Interaction, &Children, &SettingsButton), Changed<Interaction>>, mut text_query: Query<&mut Text>) {
+
+    for (interaction, children, settings_button) in interaction_query.iter() {
+        let base_size = match settings_button {
+            SettingsButton::Toggle(_) => 28.0,
+            SettingsButton::Back => 40.0
+        };
+
+        let font_size = match interaction {
+            Interaction::Pressed => base_size + 5.0,
+            Interaction::Hovered => base_size + 10.0,
+            Interaction::None => base_size,
+        };
+
+        for &child in children.iter() {
+            if let Ok(mut text) = text_query.get_mut(child) {
+                for section in &mut text.sections {
+                    section.style.font_size = font_size;
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::input::keyboard::{Key, KeyboardInput};
+use bevy::input::ButtonState;
+use bevy::prelude::*;
+
+pub struct TextInputPlugin;
+
+impl Plugin for TextInputPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TextInputSubmitEvent>();
+        app.add_systems(Update, (type_into_text_input, update_text_input_text).chain());
+    }
+}
+
+#[derive(Component)]
+pub struct TextInput {
+    pub value: String,
+    pub max_length: usize,
+    pub allowed: fn(char) -> bool,
+    // Checked on the whole value after every typed character, the character is dropped when it fails
+    pub valid: fn(&str) -> bool
+}
+
+impl TextInput {
+    pub fn new(max_length: usize) -> Self {
+        Self { value: String::new(), max_length, allowed: |character| !character.is_control(), valid: |_| true }
+    }
+
+    pub fn numeric(max_length: usize) -> Self {
+        Self { value: String::new(), max_length, allowed: |character| character.is_ascii_digit(), valid: |_| true }
+    }
+
+    fn push(&mut self, character: char) {
+        if (self.allowed)(character) && self.value.chars().count() < self.max_length {
+            self.value.push(character);
+
+            if !(self.valid)(&self.value) {
+                self.value.pop();
+            }
+        }
+    }
+}
+
+#[derive(Event)]
+pub struct TextInputSubmitEvent {
+    pub entity: Entity,
+    pub value: String
+}
+
+pub fn type_into_text_input(mut ev_keyboard: EventReader<KeyboardInput>, mut ev_submit: EventWriter<TextInputSubmitEvent>, mut input_query: Query<(Entity, &mut TextInput)>) {
+    for event in ev_keyboard.read() {
+        if event.state != ButtonState::Pressed {
+            continue;
+        }
+
+        for (entity, mut input) in &mut input_query {
+            match &event.logical_key {
+                Key::Enter => {
+                    ev_submit.send(TextInputSubmitEvent { entity, value: input.value.clone() });
+                },
+                Key::Backspace => {
+                    input.value.pop();
+                },
+                Key::Space => input.push(' '),
+                Key::Character(characters) => {
+                    for character in characters.chars() {
+                        input.push(character);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+}
+
+fn update_text_input_text(mut input_query: Query<(&TextInput, &mut Text), Changed<TextInput>>) {
+    for (input, mut text) in &mut input_query {
+        text.sections[0].value = format!("{}_", input.value);
+    }
+}
@@ -1,6 +1,7 @@
 use::bevy::prelude::*;
 
-use crate::{points::points::Points, CleanupGameStateExit, GameState, Level};
+use crate::{health::health::{Health, Lives}, player::{player::Player, player_dash::Dash}, points::points::{Combo, Points}, CleanupGameStateExit, GameState, GameplaySet, Level};
+use crate::powerups::powerups::{ActivePowerUps, PowerUp};
 
 
 pub struct UIPlugin<GameState: States> {
@@ -10,16 +11,43 @@ pub struct UIPlugin<GameState: States> {
 impl Plugin for UIPlugin<GameState> {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Game), spawn_game_ui.run_if(in_state(self.state.clone())));
-        app.add_systems(Update, (update_points_ui, update_level_ui).run_if(in_state(self.state.clone())));
+        app.add_systems(Update, (update_points_ui, update_combo_ui, update_level_ui, update_health_ui, update_dash_ui, update_power_up_ui).in_set(GameplaySet::Ui).run_if(in_state(self.state.clone())));
     }
 }
 
+// Filter for the buttons of a menu whose interaction changed this frame
+pub type InteractionChanged<T> = (Changed<Interaction>, With<T>);
+
 #[derive(Component)]
 pub struct PointsText;
 
 #[derive(Component)]
 pub struct LevelText;
 
+#[derive(Component)]
+pub struct ComboText;
+
+#[derive(Component)]
+pub struct LivesText;
+
+// Clips the red of a heart to the part of it that is still filled, so half a point of health shows as half a heart
+#[derive(Component)]
+pub struct HeartIcon(usize);
+
+#[derive(Component)]
+pub struct DashCooldownBar;
+
+// One section per power-up, empty while it is not active
+#[derive(Component)]
+pub struct PowerUpTimersText;
+
+const HEART_SLOTS: usize = 3;
+const HEART_SIZE: f32 = 24.0;
+const HEART_FULL_COLOR: Color = Color::srgb(0.85, 0.1, 0.15);
+const HEART_EMPTY_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const DASH_READY_COLOR: Color = Color::srgb(0.3, 0.8, 1.0);
+const DASH_CHARGING_COLOR: Color = Color::srgb(0.3, 0.4, 0.5);
+
 fn spawn_game_ui(mut commands: Commands) {
     commands.spawn((NodeBundle {
         style: Style {
@@ -54,6 +82,128 @@ fn spawn_game_ui(mut commands: Commands) {
             ..default()
         }, LevelText));
 
+        commands.spawn((TextBundle {
+            text: Text::from_section("", TextStyle {
+                font_size: 24.0,
+                color: Color::srgb(1.0, 0.9, 0.3),
+                ..default()
+            }),
+            ..default()
+        }, ComboText));
+
+        commands.spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::top(Val::Px(5.0)),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }).with_children(|commands| {
+            for index in 0..HEART_SLOTS {
+                commands.spawn(NodeBundle {
+                    style: Style {
+                        width: Val::Px(HEART_SIZE),
+                        height: Val::Px(HEART_SIZE),
+                        margin: UiRect::right(Val::Px(6.0)),
+                        ..default()
+                    },
+                    background_color: HEART_EMPTY_COLOR.into(),
+                    border_radius: BorderRadius::all(Val::Percent(50.0)),
+                    ..default()
+                }).with_children(|commands| {
+                    commands.spawn((NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            overflow: Overflow::clip(),
+                            ..default()
+                        },
+                        ..default()
+                    }, HeartIcon(index))).with_children(|commands| {
+                        commands.spawn(NodeBundle {
+                            style: Style {
+                                width: Val::Px(HEART_SIZE),
+                                height: Val::Px(HEART_SIZE),
+                                flex_shrink: 0.0,
+                                ..default()
+                            },
+                            background_color: HEART_FULL_COLOR.into(),
+                            border_radius: BorderRadius::all(Val::Percent(50.0)),
+                            ..default()
+                        });
+                    });
+                });
+            }
+
+            commands.spawn((TextBundle {
+                style: Style {
+                    margin: UiRect::left(Val::Px(10.0)),
+                    ..default()
+                },
+                text: Text::from_section("Lives", TextStyle {
+                    font_size: 32.0,
+                    ..default()
+                }),
+                ..default()
+            }, LivesText));
+        });
+
+        commands.spawn(NodeBundle {
+            style: Style {
+                margin: UiRect::top(Val::Px(5.0)),
+                align_items: AlignItems::Center,
+                ..default()
+            },
+            ..default()
+        }).with_children(|commands| {
+            commands.spawn(TextBundle {
+                style: Style {
+                    margin: UiRect::right(Val::Px(10.0)),
+                    ..default()
+                },
+                text: Text::from_section("Dash", TextStyle {
+                    font_size: 32.0,
+                    ..default()
+                }),
+                ..default()
+            });
+
+            // Fills up while the dash recharges
+            commands.spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(120.0),
+                    height: Val::Px(14.0),
+                    ..default()
+                },
+                background_color: HEART_EMPTY_COLOR.into(),
+                ..default()
+            }).with_children(|commands| {
+                commands.spawn((NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: DASH_READY_COLOR.into(),
+                    ..default()
+                }, DashCooldownBar));
+            });
+        });
+
+        commands.spawn((TextBundle {
+            style: Style {
+                margin: UiRect::top(Val::Px(5.0)),
+                ..default()
+            },
+            text: Text::from_sections(PowerUp::ALL.map(|power_up| TextSection::new("", TextStyle {
+                font_size: 24.0,
+                color: power_up.color(),
+                ..default()
+            }))),
+            ..default()
+        }, PowerUpTimersText));
+
     }).insert(CleanupGameStateExit);
 }
 
@@ -63,8 +213,55 @@ fn update_points_ui(mut texts: Query<&mut Text, With<PointsText>>, points: Res<P
     }
 }
 
+fn update_combo_ui(mut texts: Query<&mut Text, With<ComboText>>, combo: Res<Combo>) {
+    for mut text in &mut texts {
+        text.sections[0].value = if combo.count > 1 {
+            format!("Combo {} x{}", combo.count, combo.multiplier())
+        } else {
+            String::new()
+        };
+    }
+}
+
 fn update_level_ui(mut text: Query<&mut Text, With<LevelText>>, level: Res<Level>) {
     for mut text in &mut text {
         text.sections[0].value = format!("Level: {:?}", level.value);
     }
-}
\ No newline at end of file
+}
+
+fn update_health_ui(mut hearts: Query<(&HeartIcon, &mut Style)>, mut lives_text: Query<&mut Text, With<LivesText>>, player_query: Query<(&Health, &Lives), With<Player>>) {
+    if let Ok((health, lives)) = player_query.get_single() {
+        for (heart, mut style) in &mut hearts {
+            let filled = (health.current - heart.0 as f32).clamp(0.0, 1.0);
+            style.width = Val::Percent(filled * 100.0);
+        }
+
+        for mut text in &mut lives_text {
+            text.sections[0].value = format!("Lives: {:?}", lives.value);
+        }
+    }
+}
+
+fn update_dash_ui(mut bars: Query<(&mut Style, &mut BackgroundColor), With<DashCooldownBar>>, player_query: Query<&Dash, With<Player>>) {
+    if let Ok(dash) = player_query.get_single() {
+        for (mut style, mut color) in &mut bars {
+            style.width = Val::Percent(dash.readiness() * 100.0);
+            *color = if dash.cooldown.finished() {
+                DASH_READY_COLOR.into()
+            } else {
+                DASH_CHARGING_COLOR.into()
+            };
+        }
+    }
+}
+
+fn update_power_up_ui(mut texts: Query<&mut Text, With<PowerUpTimersText>>, power_ups: Res<ActivePowerUps>) {
+    for mut text in &mut texts {
+        for (section, power_up) in text.sections.iter_mut().zip(PowerUp::ALL) {
+            section.value = match power_ups.remaining(power_up) {
+                Some(remaining) => format!("{} {:.1}s   ", power_up.label(), remaining),
+                None => String::new()
+            };
+        }
+    }
+}